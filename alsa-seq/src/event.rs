@@ -21,12 +21,15 @@
 use libc::{
     c_int,
     c_uint,
-    c_uchar
+    c_uchar,
+    c_void
 };
 
 use midi::*;
 use alsa_sys::*;
 
+use SequencerEvent;
+
 //
 // extra seq.h constants that alsa-sys missed
 //
@@ -38,6 +41,7 @@ const SND_SEQ_ADDRESS_UNKNOWN: c_uchar = 253;
 
 const SND_SEQ_EVENT_LENGTH_MASK: c_uint = 3 << 2;
 const SND_SEQ_EVENT_LENGTH_FIXED: c_uint = 0 << 2;
+const SND_SEQ_EVENT_LENGTH_VARIABLE: c_uint = 1 << 2;
 
 pub trait TransliteratedFromCMacros {
     fn set_fixed(&mut self);
@@ -50,6 +54,9 @@ pub trait TransliteratedFromCMacros {
     fn set_button(&mut self, _type: c_uint);
 
     fn set_cc(&mut self, _type: c_uint, channel: Channel, controller_number: u16, position: u8);
+
+    // the event only points at data, which has to outlive it
+    fn set_sysex(&mut self, data: &[u8]);
 }
 
 impl TransliteratedFromCMacros for snd_seq_event_t {
@@ -106,6 +113,28 @@ impl TransliteratedFromCMacros for snd_seq_event_t {
             (*ctrl).value = position as c_int;
         }
     }
+
+    fn set_sysex(&mut self, data: &[u8]) {
+        self._type = SND_SEQ_EVENT_SYSEX as snd_seq_event_type_t;
+
+        self.flags &= !(SND_SEQ_EVENT_LENGTH_MASK as u8);
+        self.flags |= SND_SEQ_EVENT_LENGTH_VARIABLE as u8;
+
+        let ext = self.data.ext();
+        unsafe {
+            (*ext).len = data.len() as c_uint;
+            (*ext).ptr = data.as_ptr() as *mut c_void;
+        }
+    }
+}
+
+// the whole of a sysex message, F0 and F7 included
+pub fn sysex_bytes(manufacturer: Manufacturer, data: &[U7]) -> Vec<u8> {
+    let mut bytes = vec![0xF0];
+    bytes.extend(manufacturer.to_u7s());
+    bytes.extend(data.iter().map(|d| d & 0x7F));
+    bytes.push(0xF7);
+    bytes
 }
 pub trait ToSndSeqEvent {
     fn to_snd_seq_event(&self) -> Option<snd_seq_event_t>;
@@ -148,6 +177,9 @@ impl ToSndSeqEvent for Message {
             Message::PolyphonicPressure(channel, note_number, velocity) =>
                 ev.set_note(SND_SEQ_EVENT_KEYPRESS, channel, note_number, velocity, 0),
 
+            Message::ChannelPressure(channel, pressure) =>
+                ev.set_cc(SND_SEQ_EVENT_CHANPRESS, channel, 0, pressure),
+
             Message::Start =>
                 ev.set_button(SND_SEQ_EVENT_START),
 
@@ -157,9 +189,19 @@ impl ToSndSeqEvent for Message {
             Message::Stop =>
                 ev.set_button(SND_SEQ_EVENT_STOP),
 
+            Message::ControlChange(channel, cc, value) =>
+                ev.set_cc(SND_SEQ_EVENT_CONTROLLER, channel, cc as u16, value),
+
+            Message::AllNotesOff(channel) =>
+                ev.set_cc(SND_SEQ_EVENT_CONTROLLER, channel, 123, 0),
+
             Message::RPN7(channel, control_number, pos) =>
                 ev.set_cc(SND_SEQ_EVENT_CONTROLLER, channel, control_number, pos),
 
+            // the data can't live in the event, see SequencerPort::send_message()
+            Message::SysEx(..) =>
+                ev.set_sysex(&[]),
+
 
             _ => return None
         }
@@ -167,3 +209,54 @@ impl ToSndSeqEvent for Message {
         Some(ev)
     }
 }
+
+// what an input event is, roughly, for deciding whether to pass it on
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EventKind {
+    Note,
+    Controller,
+    ProgramChange,
+    Pressure,
+    PitchBend,
+    SysEx,
+    Realtime,
+    Other
+}
+
+impl SequencerEvent {
+    pub fn kind(&self) -> EventKind {
+        match self.raw._type as c_uint {
+            SND_SEQ_EVENT_NOTE | SND_SEQ_EVENT_NOTEON | SND_SEQ_EVENT_NOTEOFF =>
+                EventKind::Note,
+
+            SND_SEQ_EVENT_CONTROLLER | SND_SEQ_EVENT_CONTROL14 | SND_SEQ_EVENT_NONREGPARAM
+                | SND_SEQ_EVENT_REGPARAM =>
+                EventKind::Controller,
+
+            SND_SEQ_EVENT_PGMCHANGE =>
+                EventKind::ProgramChange,
+
+            SND_SEQ_EVENT_KEYPRESS | SND_SEQ_EVENT_CHANPRESS =>
+                EventKind::Pressure,
+
+            SND_SEQ_EVENT_PITCHBEND =>
+                EventKind::PitchBend,
+
+            SND_SEQ_EVENT_SYSEX =>
+                EventKind::SysEx,
+
+            SND_SEQ_EVENT_SONGPOS | SND_SEQ_EVENT_SONGSEL | SND_SEQ_EVENT_QFRAME
+                | SND_SEQ_EVENT_START | SND_SEQ_EVENT_CONTINUE | SND_SEQ_EVENT_STOP
+                | SND_SEQ_EVENT_CLOCK | SND_SEQ_EVENT_TUNE_REQUEST | SND_SEQ_EVENT_RESET
+                | SND_SEQ_EVENT_SENSING =>
+                EventKind::Realtime,
+
+            _ => EventKind::Other
+        }
+    }
+
+    // the client the event was sent by, compare with SequencerHandle::client_id()
+    pub fn source_client(&self) -> c_int {
+        self.raw.source.client as c_int
+    }
+}
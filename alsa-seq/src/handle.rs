@@ -18,12 +18,15 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use std::ptr::null_mut;
+use std::ptr::{self, null_mut};
 use std::ffi;
+use std::os::unix::io::RawFd;
 
+use libc::{pollfd, POLLIN};
 use alsa_sys::*;
 
 use {
+    SequencerEvent,
     SequencerHandle,
     SequencerPort,
     Error,
@@ -95,6 +98,49 @@ impl SequencerHandle {
             snd_seq_drain_output(self.raw_handle);
         }
     }
+
+    pub fn client_id(&self) -> i32 {
+        unsafe {
+            snd_seq_client_id(self.raw_handle)
+        }
+    }
+
+    // a descriptor to poll for input on. this also switches the handle to non-blocking, so
+    // input_event() can be called until it runs dry each time it's readable
+    pub fn input_fd(&self) -> Option<RawFd> {
+        let mut pfd = pollfd {
+            fd: -1,
+            events: 0,
+            revents: 0
+        };
+
+        unsafe {
+            if snd_seq_nonblock(self.raw_handle, 1) != 0 {
+                return None;
+            }
+
+            if snd_seq_poll_descriptors(self.raw_handle, &mut pfd, 1, POLLIN) != 1 {
+                return None;
+            }
+        }
+
+        Some(pfd.fd)
+    }
+
+    // the next event that arrived on any of this handle's ports, or None if there isn't one
+    pub fn input_event(&self) -> Option<SequencerEvent> {
+        let mut ev: *mut snd_seq_event_t = null_mut();
+
+        unsafe {
+            if snd_seq_event_input(self.raw_handle, &mut ev) < 0 || ev.is_null() {
+                return None;
+            }
+
+            Some(SequencerEvent {
+                raw: ptr::read(ev)
+            })
+        }
+    }
 }
 
 impl Drop for SequencerHandle {
@@ -38,6 +38,11 @@ pub struct SequencerPort<'handle> {
     handle: &'handle SequencerHandle
 }
 
+// an event as it came in on an input port
+pub struct SequencerEvent {
+    raw: snd_seq_event_t
+}
+
 #[derive(Debug)]
 pub enum Error {
     Unknown
@@ -57,4 +62,6 @@ pub use port::{
 
 mod event;
 
+pub use event::EventKind;
+
 mod test;
@@ -18,17 +18,21 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use std::ptr;
+
 use libc::*;
 
 use midi::*;
 use alsa_sys::*;
 
 use {
+    SequencerEvent,
     SequencerPort,
     Error,
 };
 
 use event::{
+    sysex_bytes,
     ToSndSeqEvent,
     TransliteratedFromCMacros
 };
@@ -38,6 +42,7 @@ bitflags! {
         const PORT_CAPABILITY_DUPLEX = (1 << 4);
         const PORT_CAPABILITY_NO_EXPORT = (1 << 7);
         const PORT_CAPABILITY_READ = (1 << 0);
+        const PORT_CAPABILITY_WRITE = (1 << 1);
         const PORT_CAPABILITY_SUBS_READ = (1 << 5);
         const PORT_CAPABILITY_SUBS_WRITE = (1 << 6);
         const PORT_CAPABILITY_SYNC_READ = (1 << 2);
@@ -71,13 +76,33 @@ impl<'handle> SequencerPort<'handle> {
             None => return Err(Error::Unknown)
         };
 
+        // sysex events only point at their bytes, which have to stay around until the event
+        // has been output
+        let sysex;
+        if let Message::SysEx(manufacturer, ref data) = *msg {
+            sysex = sysex_bytes(manufacturer, data);
+            ev.set_sysex(&sysex);
+        }
+
+        self.output(&mut ev)
+    }
+
+    // sends an event read with SequencerHandle::input_event() on unchanged, apart from who it's
+    // from and to. sysex data is still pointed at where it was read, so this has to happen
+    // before the next input_event()
+    pub fn send_event(&self, ev: &SequencerEvent) -> Result<(), Error> {
+        let mut ev = unsafe { ptr::read(&ev.raw) };
+        self.output(&mut ev)
+    }
+
+    fn output(&self, ev: &mut snd_seq_event_t) -> Result<(), Error> {
         ev.set_direct();
         ev.set_subs();
 
         ev.set_source(self.raw_handle as c_uchar);
 
         unsafe {
-            match snd_seq_event_output(self.handle.raw_handle, &mut ev) {
+            match snd_seq_event_output(self.handle.raw_handle, ev) {
                 err_code @ _ if err_code < 0 => return Err(Error::Unknown),
                 _ => {}
             }
@@ -0,0 +1,66 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+use std::cell::Cell;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// where the time comes from, so that what's driven by it can be tested without waiting on it
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+
+    // how long it's been since then, or nothing if then hasn't come yet
+    fn since(&self, then: SystemTime) -> Duration {
+        self.now().duration_since(then).unwrap_or_default()
+    }
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+// a clock that only moves when it's told to
+pub struct MockClock {
+    now: Cell<SystemTime>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        MockClock::new(UNIX_EPOCH)
+    }
+}
+
+impl MockClock {
+    pub fn new(start: SystemTime) -> Self {
+        MockClock {
+            now: Cell::new(start),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        self.now.get()
+    }
+}
@@ -0,0 +1,112 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+use std::time::{Duration, SystemTime};
+
+use base::{MaschineButton, MaschineEvent};
+
+#[derive(Copy, Clone, Default)]
+struct ButtonState {
+    down: bool,
+    changed: Option<SystemTime>,
+
+    // a change that came in too soon after the last one, waiting to see if it sticks
+    pending: Option<u8>,
+}
+
+// mechanical buttons bounce, so a button's edges are only let through once it's been in its
+// current state for `window`. a change that comes in sooner isn't thrown away, it's held onto
+// and handed back by settle() if the button is still that way once the window is up, otherwise
+// a bounce on release could leave a button stuck down
+pub struct Debounce {
+    window: Duration,
+    buttons: Vec<(MaschineButton, ButtonState)>,
+}
+
+impl Debounce {
+    pub fn new(window: Duration) -> Debounce {
+        Debounce {
+            window,
+            buttons: vec![],
+        }
+    }
+
+    fn state(&mut self, btn: MaschineButton) -> &mut ButtonState {
+        let idx = match self.buttons.iter().position(|&(b, _)| b == btn) {
+            Some(idx) => idx,
+            None => {
+                self.buttons.push((btn, ButtonState::default()));
+                self.buttons.len() - 1
+            }
+        };
+
+        &mut self.buttons[idx].1
+    }
+
+    // whether a press or release should be acted on now
+    pub fn accept(&mut self, btn: MaschineButton, down: bool, byte: u8, now: SystemTime) -> bool {
+        let window = self.window;
+        let state = self.state(btn);
+
+        if down == state.down {
+            // bounced back to where it was
+            state.pending = None;
+            return false;
+        }
+
+        let settled = match state.changed {
+            Some(changed) => now.duration_since(changed).unwrap_or_default() >= window,
+            None => true,
+        };
+
+        if !settled {
+            state.pending = Some(byte);
+            return false;
+        }
+
+        state.down = down;
+        state.changed = Some(now);
+        state.pending = None;
+        true
+    }
+
+    // held back changes whose window is up, to be fed back through accept()
+    pub fn settle(&mut self, now: SystemTime) -> Vec<MaschineEvent> {
+        let window = self.window;
+        let mut settled = vec![];
+
+        for &mut (btn, ref mut state) in self.buttons.iter_mut() {
+            let due = match state.changed {
+                Some(changed) => now.duration_since(changed).unwrap_or_default() >= window,
+                None => true,
+            };
+
+            if !due {
+                continue;
+            }
+
+            if let Some(byte) = state.pending.take() {
+                settled.push(match state.down {
+                    true => MaschineEvent::ButtonUp(btn, byte),
+                    false => MaschineEvent::ButtonDown(btn, byte),
+                });
+            }
+        }
+
+        settled
+    }
+}
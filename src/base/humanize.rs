@@ -0,0 +1,58 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+// nudges velocities up or down by up to `amount`, so repeated hits don't all land the same.
+// the generator is a xorshift, seeded so a run can be played back the same
+pub struct Humanize {
+    amount: u8,
+    state: u64,
+}
+
+impl Humanize {
+    pub fn new(amount: u8, seed: u64) -> Humanize {
+        Humanize {
+            amount,
+
+            // a xorshift started at 0 never leaves it
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    // kept to 1-127, a 0 would go out as a note off. a velocity of 0 isn't a hit, so it's
+    // left alone
+    pub fn velocity(&mut self, velocity: u8) -> u8 {
+        if velocity == 0 || self.amount == 0 {
+            return velocity;
+        }
+
+        let span = 2 * self.amount as u64 + 1;
+        let offset = (self.next() % span) as i32 - self.amount as i32;
+
+        (velocity as i32 + offset).clamp(1, 127) as u8
+    }
+}
@@ -16,8 +16,9 @@
 //  <http://www.gnu.org/licenses/>.
 
 use std::os::unix::io::RawFd;
+use std::time::{Duration, SystemTime};
 
-#[derive(Copy,Clone,Debug)]
+#[derive(Copy,Clone,Debug,PartialEq)]
 pub enum MaschineButton {
     F8,
     F7,
@@ -231,12 +232,74 @@ pub enum MaschineButton {
     P8,
 }
 
+// input as decoded from the device, before it's handed to a MaschineHandler
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum MaschineEvent {
+    PadPressed(usize, f32),
+    PadAftertouch(usize, f32),
+    PadReleased(usize),
 
+    EncoderStep(usize, i32),
 
+    ButtonDown(MaschineButton, u8),
+    ButtonUp(MaschineButton, u8),
+}
+
+impl MaschineEvent {
+    pub fn dispatch(self, maschine: &mut dyn Maschine, handler: &mut dyn MaschineHandler) {
+        match self {
+            MaschineEvent::PadPressed(pad, pressure) => handler.pad_pressed(maschine, pad, pressure),
+            MaschineEvent::PadAftertouch(pad, pressure) =>
+                handler.pad_aftertouch(maschine, pad, pressure),
+            MaschineEvent::PadReleased(pad) => handler.pad_released(maschine, pad),
+
+            MaschineEvent::EncoderStep(encoder, delta) =>
+                handler.encoder_step(maschine, encoder, delta),
+
+            MaschineEvent::ButtonDown(btn, byte) => handler.button_down(maschine, btn, byte),
+            MaschineEvent::ButtonUp(btn, byte) => handler.button_up(maschine, btn, byte),
+        }
+    }
+}
+
+// what a device's lights were last set to, as asked for rather than as shown: the colour
+// before any dimming or palette snapping, and buttons that only have a brightness keep the
+// colour they were given anyway
+#[derive(Clone,Debug,PartialEq)]
+pub struct LightFrame {
+    pub pads: [(u32, f32); 16],
+
+    // only the buttons that have been set since the lights were last cleared
+    pub buttons: Vec<(MaschineButton, u32, f32)>,
+}
+
+impl Default for LightFrame {
+    fn default() -> Self {
+        LightFrame {
+            pads: [(0, 0.0); 16],
+            buttons: vec![],
+        }
+    }
+}
+
+impl LightFrame {
+    pub fn set_button(&mut self, btn: MaschineButton, color: u32, brightness: f32) {
+        self.buttons.retain(|&(b, _, _)| b != btn);
+        self.buttons.push((btn, color, brightness));
+    }
+
+    pub fn button(&self, btn: MaschineButton) -> Option<(u32, f32)> {
+        self.buttons.iter()
+            .find(|&&(b, _, _)| b == btn)
+            .map(|&(_, color, brightness)| (color, brightness))
+    }
+}
 
 pub trait Maschine {
     fn get_fd(&self) -> RawFd;
 
+    // Err for pads the device doesn't have
+    #[allow(clippy::result_unit_err)]
     fn get_pad_pressure(&self, pad_idx: usize) -> Result<f32, ()>;
 
     fn get_midi_note_base(&self) -> u8;
@@ -245,10 +308,42 @@ pub trait Maschine {
     fn set_pad_light(&mut self, pad_idx: usize, color: u32, brightness: f32);
     fn set_button_light(&mut self, btn: MaschineButton, color: u32, brightness: f32);
 
+    // everything's off on devices without lights
+    fn get_lights(&self) -> LightFrame { LightFrame::default() }
+
+    // how long lights take to ease from off to fully on, 0 for straight away. devices that
+    // can't fade set them straight away whatever it is
+    fn set_led_fade(&mut self, _fade: Duration) {}
+
+    // the exponent a device puts its light levels through so they look as bright as they're
+    // asked to, 1.0 for none. does nothing on devices without the lights to need it
+    fn set_led_gamma(&mut self, _gamma: f32) {}
+
     fn readable(&mut self, _: &mut dyn MaschineHandler);
 
+    fn get_screen(&self) -> &[u8];
+    fn get_screen_mut(&mut self) -> &mut [u8];
+
+    // sends whatever's been drawn into the screen buffer
+    fn send_screen(&mut self);
+
+    // forget what the screen is showing, so the next send_screen sends all of it
+    fn force_full_redraw(&mut self);
+
     fn clear_screen(&mut self);
+    fn clear_lights(&mut self);
     fn write_lights(&mut self);
+
+    // whether any light has changed since the last write_lights
+    fn lights_dirty(&self) -> bool;
+
+    // false once the device has gone away, e.g. been unplugged
+    fn connected(&self) -> bool;
+
+    // 0.0 to 1.0, rounded to whatever levels the display has. does nothing on devices whose
+    // display brightness can't be set
+    fn set_screen_brightness(&mut self, _brightness: f32) {}
+
     fn write_screen(&mut self);
 }
 
@@ -263,5 +358,20 @@ pub trait MaschineHandler {
     fn button_down(&mut self, _: &mut dyn Maschine, button: MaschineButton, byte: u8) {}
     fn button_up(&mut self, _: &mut dyn Maschine, button: MaschineButton, byte: u8) {}
 
-    fn read_input(&mut self, _: &mut dyn Maschine) {}
+    // called just before the device reads a report, with when it was noticed
+    fn report_arrived(&mut self, at: SystemTime) {}
+
+    // read_input() is called with whichever of these is readable
+    fn get_fds(&self) -> Vec<RawFd> { Vec::new() }
+    fn read_input(&mut self, _: &mut dyn Maschine, fd: RawFd) {}
+
+    // called every RunConfig::tick_interval
+    fn tick(&mut self, _: &mut dyn Maschine) {}
+
+    // called at the end of every pass through run()'s loop, to send whatever was batched up
+    // during it
+    fn flush(&mut self) {}
+
+    // called once the device is gone, just before run() exits. the device can't be used
+    fn disconnected(&mut self) {}
 }
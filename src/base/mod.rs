@@ -17,9 +17,11 @@
 
 pub mod maschine;
 pub use self::maschine::{
+    LightFrame,
     Maschine,
     MaschineHandler,
-    MaschineButton
+    MaschineButton,
+    MaschineEvent
 };
 
 pub mod maschine_pad;
@@ -27,3 +29,16 @@ pub use self::maschine_pad::{
     MaschinePad,
     MaschinePadStateTransition
 };
+
+pub mod screen;
+
+pub mod clock;
+pub use self::clock::{
+    Clock,
+    MockClock,
+    SystemClock
+};
+
+pub mod debounce;
+pub mod humanize;
+pub mod release_lockout;
@@ -0,0 +1,71 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+use std::time::{Duration, SystemTime};
+
+use base::MaschineEvent;
+
+// a pad let go of quickly can read above the threshold again for a moment, which would play
+// it again straight after its note off. for `window` after a release, a press on the same pad
+// is taken for that, and it's swallowed along with its aftertouch and release
+pub struct ReleaseLockout {
+    window: Duration,
+    released: [Option<SystemTime>; 16],
+
+    // pads partway through a swallowed press
+    swallowing: [bool; 16],
+}
+
+impl ReleaseLockout {
+    pub fn new(window: Duration) -> ReleaseLockout {
+        ReleaseLockout {
+            window,
+            released: [None; 16],
+            swallowing: [false; 16],
+        }
+    }
+
+    // whether the event should be acted on. anything but pad events always is
+    pub fn accept(&mut self, ev: MaschineEvent, now: SystemTime) -> bool {
+        match ev {
+            MaschineEvent::PadPressed(pad, _) => {
+                let locked = match self.released[pad] {
+                    Some(at) => now.duration_since(at).unwrap_or_default() < self.window,
+                    None => false,
+                };
+
+                self.swallowing[pad] = locked;
+                !locked
+            }
+
+            MaschineEvent::PadAftertouch(pad, _) => !self.swallowing[pad],
+
+            // the lockout runs from the real release, a swallowed one doesn't make it longer
+            MaschineEvent::PadReleased(pad) => {
+                if self.swallowing[pad] {
+                    self.swallowing[pad] = false;
+                    return false;
+                }
+
+                self.released[pad] = Some(now);
+                true
+            }
+
+            _ => true,
+        }
+    }
+}
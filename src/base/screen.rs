@@ -0,0 +1,138 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+// screens are one bit per pixel, row by row, with the leftmost pixel of each byte in its top
+// bit. the mikro's is 256x64
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 64;
+const SCREEN_STRIDE: usize = SCREEN_WIDTH / 8;
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+// 5x7 glyphs, one byte per column with the top row in the lowest bit. lowercase is drawn as
+// uppercase and anything missing as '?'
+const FONT: &[(char, [u8; GLYPH_WIDTH])] = &[
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('#', [0x14, 0x7F, 0x14, 0x7F, 0x14]),
+    ('%', [0x23, 0x13, 0x08, 0x64, 0x62]),
+    ('(', [0x00, 0x1C, 0x22, 0x41, 0x00]),
+    (')', [0x00, 0x41, 0x22, 0x1C, 0x00]),
+    ('+', [0x08, 0x08, 0x3E, 0x08, 0x08]),
+    ('-', [0x08, 0x08, 0x08, 0x08, 0x08]),
+    ('.', [0x00, 0x60, 0x60, 0x00, 0x00]),
+    ('/', [0x20, 0x10, 0x08, 0x04, 0x02]),
+    ('0', [0x3E, 0x51, 0x49, 0x45, 0x3E]),
+    ('1', [0x00, 0x42, 0x7F, 0x40, 0x00]),
+    ('2', [0x42, 0x61, 0x51, 0x49, 0x46]),
+    ('3', [0x21, 0x41, 0x45, 0x4B, 0x31]),
+    ('4', [0x18, 0x14, 0x12, 0x7F, 0x10]),
+    ('5', [0x27, 0x45, 0x45, 0x45, 0x39]),
+    ('6', [0x3C, 0x4A, 0x49, 0x49, 0x30]),
+    ('7', [0x01, 0x71, 0x09, 0x05, 0x03]),
+    ('8', [0x36, 0x49, 0x49, 0x49, 0x36]),
+    ('9', [0x06, 0x49, 0x49, 0x29, 0x1E]),
+    (':', [0x00, 0x36, 0x36, 0x00, 0x00]),
+    ('=', [0x14, 0x14, 0x14, 0x14, 0x14]),
+    ('?', [0x02, 0x01, 0x51, 0x09, 0x06]),
+    ('A', [0x7E, 0x11, 0x11, 0x11, 0x7E]),
+    ('B', [0x7F, 0x49, 0x49, 0x49, 0x36]),
+    ('C', [0x3E, 0x41, 0x41, 0x41, 0x22]),
+    ('D', [0x7F, 0x41, 0x41, 0x22, 0x1C]),
+    ('E', [0x7F, 0x49, 0x49, 0x49, 0x41]),
+    ('F', [0x7F, 0x09, 0x09, 0x09, 0x01]),
+    ('G', [0x3E, 0x41, 0x49, 0x49, 0x7A]),
+    ('H', [0x7F, 0x08, 0x08, 0x08, 0x7F]),
+    ('I', [0x00, 0x41, 0x7F, 0x41, 0x00]),
+    ('J', [0x20, 0x40, 0x41, 0x3F, 0x01]),
+    ('K', [0x7F, 0x08, 0x14, 0x22, 0x41]),
+    ('L', [0x7F, 0x40, 0x40, 0x40, 0x40]),
+    ('M', [0x7F, 0x02, 0x0C, 0x02, 0x7F]),
+    ('N', [0x7F, 0x04, 0x08, 0x10, 0x7F]),
+    ('O', [0x3E, 0x41, 0x41, 0x41, 0x3E]),
+    ('P', [0x7F, 0x09, 0x09, 0x09, 0x06]),
+    ('Q', [0x3E, 0x41, 0x51, 0x21, 0x5E]),
+    ('R', [0x7F, 0x09, 0x19, 0x29, 0x46]),
+    ('S', [0x46, 0x49, 0x49, 0x49, 0x31]),
+    ('T', [0x01, 0x01, 0x7F, 0x01, 0x01]),
+    ('U', [0x3F, 0x40, 0x40, 0x40, 0x3F]),
+    ('V', [0x1F, 0x20, 0x40, 0x20, 0x1F]),
+    ('W', [0x3F, 0x40, 0x38, 0x40, 0x3F]),
+    ('X', [0x63, 0x14, 0x08, 0x14, 0x63]),
+    ('Y', [0x07, 0x08, 0x70, 0x08, 0x07]),
+    ('Z', [0x61, 0x51, 0x49, 0x45, 0x43]),
+    ('_', [0x40, 0x40, 0x40, 0x40, 0x40]),
+];
+
+fn glyph(c: char) -> [u8; GLYPH_WIDTH] {
+    let c = c.to_ascii_uppercase();
+
+    match FONT.iter().find(|&&(fc, _)| fc == c) {
+        Some(&(_, columns)) => columns,
+        None => glyph('?'),
+    }
+}
+
+// pixels off the edge of the screen are dropped
+pub fn set_pixel(screen: &mut [u8], x: usize, y: usize, on: bool) {
+    if x >= SCREEN_WIDTH || y >= SCREEN_HEIGHT {
+        return;
+    }
+
+    let idx = y * SCREEN_STRIDE + x / 8;
+    let bit = 0x80 >> (x % 8);
+
+    if on {
+        screen[idx] |= bit;
+    } else {
+        screen[idx] &= !bit;
+    }
+}
+
+pub fn clear_rect(screen: &mut [u8], x: usize, y: usize, width: usize, height: usize) {
+    for py in y..y + height {
+        for px in x..x + width {
+            set_pixel(screen, px, py, false);
+        }
+    }
+}
+
+// draws text with its top left corner at x, y, every font pixel drawn scale pixels square.
+// returns the width drawn
+pub fn draw_text(screen: &mut [u8], x: usize, y: usize, scale: usize, text: &str) -> usize {
+    let advance = (GLYPH_WIDTH + 1) * scale;
+
+    for (n, c) in text.chars().enumerate() {
+        let gx = x + n * advance;
+
+        for (col, bits) in glyph(c).iter().enumerate() {
+            for row in 0..GLYPH_HEIGHT {
+                if bits & (1 << row) == 0 {
+                    continue;
+                }
+
+                for sy in 0..scale {
+                    for sx in 0..scale {
+                        set_pixel(screen, gx + col * scale + sx, y + row * scale + sy, true);
+                    }
+                }
+            }
+        }
+    }
+
+    text.chars().count() * advance
+}
@@ -0,0 +1,1593 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+use std::env;
+use std::fs;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::Duration;
+
+use alsa_seq::EventKind;
+use hsl::HSL;
+use midi::*;
+
+use base::MaschineButton;
+
+use {
+    osc_button_to_btn_map, theme_gradient, AftertouchHeld, AftertouchMode, AutoRepeat,
+    ButtonAction, DriverMode, EncoderFormat, EncoderMidi, EncoderMode, InputSource,
+    NoteRangePolicy, OscKind, OscUnknown, PadLight, PressureShape, RollOverflow, SharedNotePolicy,
+    Subdivision, DEFAULT_DEBOUNCE, GM_DRUM_PAD_NOTES, MAX_LED_GAMMA, MIDI_CLIENT_NAME,
+    MIDI_IN_PORT_NAME, MIDI_OUT_PORT_NAME, MIN_LED_GAMMA, OSC_LISTEN_PORT, OSC_SEND_PORT,
+    PAD_RELEASED_BRIGHTNESS,
+};
+
+// the controllers RPN messages are made of, which nothing else sharing a channel with the
+// buttons can use without garbling them
+const RPN_CONTROLLERS: [U7; 4] = [6, 38, 100, 101];
+
+// something wrong with a configuration that parsed, from Config::validate()
+#[derive(Clone, Debug, PartialEq)]
+pub enum Problem {
+    // it can't work as asked, so there's no starting with it
+    Error(String),
+
+    // it'll work, but probably not as meant
+    Warning(String),
+}
+
+// more than this and it's flooding the network rather than getting through it
+const MAX_OSC_REPEATS: u64 = 10;
+
+// MASCHINE_OSC_SEND sets --osc-send, and so on
+const ENV_PREFIX: &str = "MASCHINE_";
+
+// the environment's MASCHINE_ variables. anything else in there is none of our business, and
+// isn't required to be UTF-8 either
+pub fn env_vars() -> Vec<(String, String)> {
+    env::vars_os()
+        .filter_map(|(name, value)| {
+            let name = name.into_string().ok()?;
+            if !name.starts_with(ENV_PREFIX) {
+                return None;
+            }
+
+            match value.into_string() {
+                Ok(value) => Some((name, value)),
+                Err(_) => {
+                    println!(" :: ignoring {}, it isn't valid UTF-8", name);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn unknown_option(key: &str) -> String {
+    format!("unknown option \"{}\"", key)
+}
+
+// options which are switched on just by being present on the command line
+const FLAGS: &[&str] = &[
+    "coalesce",
+    "dark-start",
+    "encoder-readout",
+    "erase-as-stop",
+    "keep-screen",
+    "latency",
+    "learn",
+    "led-follow",
+    "led-palette",
+    "light-test",
+    "metronome",
+    "no-midi-batch",
+    "no-picture",
+    "no-screen",
+    "osc-notes",
+    "osc-pressure",
+    "note-grid",
+    "page-octaves",
+    "self-test",
+    "version",
+    "watchdog-exit",
+];
+
+pub const OPTIONS_HELP: &str = "\
+options:
+  --aftertouch M     send pad aftertouch as poly pressure, a controller or channel pressure
+                     (poly|cc|channel)
+  --aftertouch-cc CC the controller for --aftertouch cc (default 11, expression)
+  --aftertouch-threshold F
+                     send no aftertouch for pressures below this, 0.0-1.0, stretching the
+                     rest over the full range
+  --aftertouch-held H
+                     how the pads held on a channel make up its controller or channel pressure:
+                     each their own, the hardest, the latest hit, their average or their sum
+                     (each|max|latest|average|sum)
+  --auto-repeat BUTTONS=DELAY:INTERVAL
+                     make the comma separated buttons fire again while held, first after DELAY
+                     ms and then every INTERVAL ms, can be repeated
+  --button-channel BUTTONS=CH
+                     send the comma separated buttons' MIDI on this channel, can be repeated
+  --button-note BUTTON=NOTE[:VELOCITY]
+                     make the button play a note on its channel instead of sending its RPN,
+                     at a fixed velocity (default 127), can be repeated
+  --choke PADS       make the comma separated pads (0-15) cut each other off, can be repeated
+  --config FILE      read options from a file, one a line as on the command line but without
+                     the dashes, e.g. \"osc-send 9000\", and again on /maschine/reload
+  --coalesce         sum encoder steps and sample aftertouch, sending once per tick
+  --dark-start       keep the pads off until one has been played, then light them as usual
+  --debounce MS      ignore buttons changing again sooner than this, 0 to turn it off (default 5)
+  --encoder-format F send /maschine/encoder's steps as an int, a float, the 0-127 position
+                     alone, or \"+1\" and \"-1\" strings (int|float|absolute|direction)
+  --encoder-mode M   report encoder steps alone or with a position that wraps or stops at
+                     0 and 127, turn the screen brightness or the note base with it, or step
+                     through --encoder-values, wrapping or stopping at the ends
+                     (relative|wrap|clamp|screen|note-base|list-wrap|list-clamp)
+  --encoder-values VALUES
+                     the comma separated values, 0-127, the list modes step through
+  --encoder-midi M   send the value the list modes select as a program change or as this
+                     controller, on the encoder's --button-channel (program|CC)
+  --encoder-fine F   scale turns down by this, 0.0-1.0, while the encoder is pushed in
+  --encoder-readout  show the encoder's value on the screen while it's being turned
+  --erase-as-stop    report the erase button as stop, with stop's RPN, like older versions did
+  --idle-brightness F
+                     brightness of released pads, 0.0-1.0
+  --group-color RRGGBB
+                     light the group button of the selected note base in this colour, and
+                     dim the others
+  --idle-color RRGGBB
+                     light released pads in this colour, instead of the one they're hit in
+  --humanize N       move each pad hit's velocity up or down by a random amount up to this,
+                     0 to leave them as played (default 0)
+  --humanize-seed N  start --humanize's random numbers from this, to get the same ones every
+                     run (default: the time)
+  --input S          read pads and buttons from the device or the keyboard (hid|keyboard)
+  --keep-screen      leave what's on the screen from before alone at startup, rather than
+                     clearing it and drawing the picture
+  --latency          measure how long pad and button input takes to go out as MIDI, and
+                     report it every 5 seconds
+  --learn            print what each pad and button sends when touched, instead of sending it
+  --led-follow       light buttons while they're held, instead of leaving them to OSC
+  --led-fade MS      ease lights between brightnesses, taking this long from off to fully on,
+                     0 to set them straight away (default 0)
+  --led-gamma F      put the pad LEDs' levels through this exponent, 1.0-3.0, for brightnesses
+                     that look as they're asked for (default 1.0, none)
+  --led-palette      snap pad colours to the nearest of a fixed set of 17, rather than showing
+                     them as asked for
+  --light-interval MS
+                     the shortest time between light updates (default 16)
+  --light-refresh MS resend the lights this often even when they haven't changed (default 1000)
+  --light-test       sweep through the pad and button lights at startup
+  --max-hold MS      release pads held longer than this, as a guard against stuck notes
+  --max-poly N       play at most this many pad notes at once, ending the oldest to make room
+                     for another, 0 for no limit (default 0)
+  --metronome        click on every beat of the tapped tempo, with an accent on the first of
+                     every 4
+  --metronome-notes BEAT,ACCENT
+                     the notes the metronome clicks with (default 77,76, the GM wood blocks)
+  --metronome-channel CH
+                     the channel the metronome clicks on (default 10)
+  --mode-button BUTTON=MODE
+                     make the button switch the pads to this mode, lit while it's the one
+                     they're in, can be repeated (normal|one-shot|latch)
+  --modifiers NAMES  the comma separated buttons that count as modifiers, whose state goes out
+                     with every button over OSC (default shift)
+  --midi-name NAME   the ALSA client name, to tell several instances apart (default maschine.rs)
+  --midi-out-port NAME
+                     the name of the MIDI output port (default \"Pads MIDI\")
+  --midi-in-port NAME
+                     the name of the MIDI input port (default \"input\")
+  --midi-thru TYPES  pass MIDI from the input port on to the output port, all of it or the
+                     comma separated types (note,cc,program,pressure,pitchbend,sysex,realtime)
+  --min-note MS      hold back the note off of a pad let go of sooner than this after it was
+                     hit, until it's been this long, for synths that click on very short notes
+  --note-range P     pads above MIDI note 127 are clamped to it or dropped (clamp|drop)
+  --one-shot MS      play every pad hit for this long however long the pad is held, 0 to turn
+                     it off
+  --osc-unknown U    ignore, log or also echo back OSC messages with unknown paths
+                     (ignore|log|echo)
+  --osc-notes        send the pads' notes over OSC as well as MIDI, as /maschine/note
+  --osc-pressure     send each pad's pressure over OSC as it changes, once per tick at most
+  --osc-repeat KINDS=N
+                     send the comma separated kinds of outgoing OSC message N times each, for
+                     lossy networks, can be repeated (button,encoder,pressure,other)
+  --osc-listen ADDR  receive OSC on this host:port or port instead of 127.0.0.1:42434
+  --osc-send ADDRS   send OSC to these comma separated host:port or port addresses instead of
+                     127.0.0.1:42435, can be repeated
+  --page-octaves     make the page buttons shift the pads down and up an octave
+  --pad-channels CHANNELS
+                     MIDI channels for the pads, one for all of them or 16 comma separated
+  --pad-light L      held pads follow their pressure or hold its peak (follow|peak)
+  --pad-notes NOTES  16 comma separated notes for the pads, from the top left, instead of the
+                     note base, or gm for the General MIDI drum kit
+  --pad-zones PAD=VELOCITY:NOTE,...
+                     make the pad play a different note for each band of velocities, each
+                     starting at its VELOCITY, can be repeated
+  --pressure P       how pad pressure turns into velocity: linear, exp:POWER, or
+                     const:PRESSURE for the same velocity every time (default exp:0.4)
+  --quantize Q       hold pad hits back to the next 1/4, 1/8, 1/16 or 1/32 note at the tapped
+                     tempo (default 120 bpm)
+  --release-lockout MS
+                     ignore a pad hit this soon after the pad was let go, as the sensor
+                     bouncing back rather than a new hit, 0 to turn it off (default 0)
+  --roll-limit N     send at most this many pad note ons per tick, for rolls across many pads
+  --roll-overflow O  hold the hits over --roll-limit back to the next ticks, or drop them
+                     (queue|drop)
+  --shared-notes P   when pads play the same note, end it when the last of them is let go, or
+                     each time one is (last|each)
+  --sustain-button NAME
+                     hold the pads' notes while this button is held, like a sustain pedal,
+                     ending them when it's let go
+  --smoothing F      smooth out aftertouch, from 0.0 for none towards 1.0 for heavy
+  --self-test        check that OSC messages get through to the lights and settings, without a
+                     device, then exit
+  --shift-layer NOTES
+                     16 comma separated notes the pads play while shift is held
+  --no-midi-batch    send each MIDI message as it's made, instead of all of those made on one
+                     pass through the event loop at once
+  --no-picture       don't draw the startup picture on the screen
+  --no-screen        leave the screen alone entirely
+  --note-grid        show the note each pad plays on the screen, instead of the picture
+  --velocity-table FILE
+                     map pad pressure to velocity with 128 comma separated velocities
+  --velocity-scale F multiply velocities and aftertouch by this, after --pressure or
+                     --velocity-table (default 1.0)
+  --toggle-buttons NAMES
+                     light the comma separated buttons brightly after one press and dimly
+                     after the next, for transport buttons
+  --toggle-on-color RRGGBB
+  --toggle-off-color RRGGBB
+                     what colour --toggle-buttons are lit in while on and off (default white)
+  --theme NAME=COLORS
+                     add a /maschine/theme palette of 16 comma separated RRGGBB colours, or
+                     a gradient between 2 of them, can be repeated
+  --version          print version and build information, then exit
+  --watchdog MS      complain when the event loop hasn't run for this long
+  --watchdog-exit    exit instead, so a supervisor can restart and reset the device
+
+every option can also be set from the environment, as MASCHINE_ and its name in capitals with
+underscores, e.g. MASCHINE_OSC_SEND=9000 or MASCHINE_PAD_CHANNELS=10, and the device as
+MASCHINE_DEVICE. flags take on or off, and variables naming no option are ignored. options on
+the command line win over the --config file, which wins over the environment, which wins over
+the defaults";
+
+pub struct Config {
+    pub device: Option<String>,
+    pub config_file: Option<String>,
+
+    pub show_version: bool,
+    pub self_test: bool,
+
+    pub input: InputSource,
+    pub osc_listen: SocketAddr,
+    pub osc_send: Vec<SocketAddr>,
+    pub osc_unknown: OscUnknown,
+    pub osc_notes: bool,
+    pub osc_pressure: bool,
+    pub osc_repeats: Vec<(OscKind, u32)>,
+
+    pub choke_groups: [Option<u8>; 16],
+
+    pub aftertouch: Option<AftertouchMode>,
+    pub aftertouch_cc: U7,
+    pub aftertouch_held: AftertouchHeld,
+    pub aftertouch_threshold: f32,
+
+    pub button_channels: Vec<(String, Channel)>,
+    pub button_notes: Vec<(String, U7, U7)>,
+    pub auto_repeats: Vec<(MaschineButton, AutoRepeat)>,
+    pub pad_channels: [Channel; 16],
+
+    pub coalesce: bool,
+    pub dark_start: bool,
+    pub debounce: Duration,
+    pub draw_picture: bool,
+    pub keep_screen: bool,
+    pub midi_batch: bool,
+    pub screen: bool,
+    pub encoder_fine: f32,
+    pub encoder_format: EncoderFormat,
+    pub encoder_mode: EncoderMode,
+    pub encoder_readout: bool,
+    pub encoder_values: Vec<U7>,
+    pub encoder_midi: Option<EncoderMidi>,
+    pub erase_as_stop: bool,
+    pub latency: bool,
+    pub learn: bool,
+    pub led_follow: bool,
+    pub led_fade: Duration,
+    pub led_gamma: f32,
+    pub led_palette: bool,
+    pub page_octaves: bool,
+    pub light_test: bool,
+
+    pub group_color: Option<u32>,
+    pub humanize: Option<u8>,
+    pub humanize_seed: Option<u64>,
+    pub idle_brightness: f32,
+    pub idle_color: Option<HSL>,
+    pub light_interval: Duration,
+    pub light_refresh: Duration,
+    pub max_hold: Option<Duration>,
+    pub max_poly: Option<usize>,
+    pub min_note: Option<Duration>,
+    pub one_shot: Option<Duration>,
+    pub metronome: bool,
+    pub metronome_notes: (U7, U7),
+    pub metronome_channel: Channel,
+    pub midi_name: String,
+    pub midi_out_port: String,
+    pub midi_in_port: String,
+    pub midi_thru: Vec<EventKind>,
+    pub note_grid: bool,
+    pub note_range: NoteRangePolicy,
+    pub pad_light: PadLight,
+    pub pad_notes: Option<[U7; 16]>,
+    pub pad_zones: [Vec<(U7, U7)>; 16],
+    pub pressure: Option<PressureShape>,
+    pub modifiers: Vec<MaschineButton>,
+    pub mode_buttons: Vec<(MaschineButton, DriverMode)>,
+    pub quantize: Option<Subdivision>,
+    pub release_lockout: Duration,
+    pub roll_limit: Option<usize>,
+    pub roll_overflow: RollOverflow,
+    pub shared_notes: SharedNotePolicy,
+    pub shift_layer: Option<[U7; 16]>,
+    pub smoothing: f32,
+    pub sustain_button: Option<MaschineButton>,
+    pub themes: Vec<(String, [HSL; 16])>,
+    pub toggle_buttons: Vec<MaschineButton>,
+    pub toggle_on_color: u32,
+    pub toggle_off_color: u32,
+    pub velocity_scale: f32,
+    pub velocity_table: Option<String>,
+    pub watchdog: Option<Duration>,
+    pub watchdog_exit: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            device: None,
+            config_file: None,
+
+            show_version: false,
+            self_test: false,
+
+            input: InputSource::Hid,
+            osc_listen: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, OSC_LISTEN_PORT)),
+            osc_send: vec![],
+            osc_unknown: OscUnknown::Ignore,
+            osc_notes: false,
+            osc_pressure: false,
+            osc_repeats: vec![],
+
+            choke_groups: [None; 16],
+
+            aftertouch: None,
+            aftertouch_cc: 11,
+            aftertouch_held: AftertouchHeld::Each,
+            aftertouch_threshold: 0.0,
+
+            button_channels: vec![],
+            button_notes: vec![],
+            auto_repeats: vec![],
+            pad_channels: [Ch1; 16],
+
+            coalesce: false,
+            dark_start: false,
+            debounce: DEFAULT_DEBOUNCE,
+            draw_picture: true,
+            keep_screen: false,
+            midi_batch: true,
+            screen: true,
+            encoder_fine: 1.0,
+            encoder_format: EncoderFormat::Int,
+            encoder_mode: EncoderMode::Relative,
+            encoder_values: vec![],
+            encoder_midi: None,
+            encoder_readout: false,
+            erase_as_stop: false,
+            latency: false,
+            learn: false,
+            led_follow: false,
+            led_fade: Duration::ZERO,
+            led_gamma: 1.0,
+            led_palette: false,
+            page_octaves: false,
+            light_test: false,
+
+            group_color: None,
+            humanize: None,
+            humanize_seed: None,
+            idle_brightness: PAD_RELEASED_BRIGHTNESS,
+            idle_color: None,
+            light_interval: Duration::from_millis(16),
+            light_refresh: Duration::from_secs(1),
+            max_hold: None,
+            max_poly: None,
+            min_note: None,
+            one_shot: None,
+            metronome: false,
+            metronome_notes: (77, 76),
+            metronome_channel: Ch10,
+            midi_name: MIDI_CLIENT_NAME.to_string(),
+            midi_out_port: MIDI_OUT_PORT_NAME.to_string(),
+            midi_in_port: MIDI_IN_PORT_NAME.to_string(),
+            midi_thru: vec![],
+            note_grid: false,
+            note_range: NoteRangePolicy::Drop,
+            pad_light: PadLight::Follow,
+            pad_notes: None,
+            pad_zones: Default::default(),
+            pressure: None,
+            modifiers: vec![MaschineButton::Shift],
+            mode_buttons: vec![],
+            quantize: None,
+            release_lockout: Duration::ZERO,
+            roll_limit: None,
+            roll_overflow: RollOverflow::Queue,
+            shared_notes: SharedNotePolicy::Last,
+            shift_layer: None,
+            smoothing: 0.0,
+            sustain_button: None,
+            themes: vec![],
+            toggle_buttons: vec![],
+            toggle_on_color: 0xFFFFFF,
+            toggle_off_color: 0xFFFFFF,
+            velocity_scale: 1.0,
+            velocity_table: None,
+            watchdog: None,
+            watchdog_exit: false,
+        }
+    }
+}
+
+const MIDI_THRU_ALL: [EventKind; 7] = [
+    EventKind::Note,
+    EventKind::Controller,
+    EventKind::ProgramChange,
+    EventKind::Pressure,
+    EventKind::PitchBend,
+    EventKind::SysEx,
+    EventKind::Realtime,
+];
+
+pub const CHANNELS: [Channel; 16] = [
+    Ch1, Ch2, Ch3, Ch4, Ch5, Ch6, Ch7, Ch8, Ch9, Ch10, Ch11, Ch12, Ch13, Ch14, Ch15, Ch16,
+];
+
+// channels are counted from 1 on the command line, like everywhere outside the protocol
+fn parse_channel(key: &str, value: &str) -> Result<Channel, String> {
+    match value.trim().parse::<usize>() {
+        Ok(ch @ 1..=16) => Ok(CHANNELS[ch - 1]),
+        _ => Err(format!("{} expects channels 1-16, got \"{}\"", key, value)),
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "1" | "on" | "true" | "yes" => Ok(true),
+        "0" | "off" | "false" | "no" => Ok(false),
+        _ => Err(format!("{} expects on/off, got \"{}\"", key, value)),
+    }
+}
+
+fn parse_f32(key: &str, value: &str) -> Result<f32, String> {
+    value
+        .parse::<f32>()
+        .map_err(|_| format!("{} expects a number, got \"{}\"", key, value))
+}
+
+fn parse_color(key: &str, value: &str) -> Result<u32, String> {
+    let color = value.trim();
+    let color = color.strip_prefix('#').unwrap_or(color);
+
+    match u32::from_str_radix(color, 16) {
+        Ok(rgb @ 0..=0xFFFFFF) if color.len() == 6 => Ok(rgb),
+        _ => Err(format!("{} expects RRGGBB colours, got \"{}\"", key, color)),
+    }
+}
+
+// 16 comma separated notes, one for each pad
+fn parse_pad_notes(key: &str, value: &str) -> Result<[U7; 16], String> {
+    let notes: Vec<_> = value.split(',').map(|note| note.trim()).collect();
+    if notes.len() != 16 {
+        return Err(format!("{} expects 16 notes, got {}", key, notes.len()));
+    }
+
+    let mut pad_notes = [0 as U7; 16];
+    for (pad, note) in notes.iter().enumerate() {
+        pad_notes[pad] = match note.parse::<U7>() {
+            Ok(note @ 0..=127) => note,
+            _ => return Err(format!("{} expects notes 0-127, got \"{}\"", key, note)),
+        };
+    }
+
+    Ok(pad_notes)
+}
+
+// a bare port is on this machine
+fn parse_addr(key: &str, addr: &str) -> Result<SocketAddr, String> {
+    let parsed = match addr.parse::<u16>() {
+        Ok(port) => Ok(SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port))),
+        Err(_) => addr.parse::<SocketAddr>(),
+    };
+
+    parsed.map_err(|_| format!("{} expects host:port, got \"{}\"", key, addr))
+}
+
+fn parse_u64(key: &str, value: &str) -> Result<u64, String> {
+    value
+        .parse::<u64>()
+        .map_err(|_| format!("{} expects a whole number, got \"{}\"", key, value))
+}
+
+// a --config file's options with the lines they're on, written "name value" or "name=value",
+// or just the name for a flag. blank lines and lines starting with # are skipped
+pub fn parse_config_file(text: &str) -> Result<Vec<(usize, String, String)>, String> {
+    let mut options = vec![];
+
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("--").unwrap_or(line);
+        let (name, value) = match line.split_once(|c: char| c == '=' || c.is_whitespace()) {
+            Some((name, value)) => (name, value.trim()),
+            None if FLAGS.contains(&line) => (line, "on"),
+            None => {
+                return Err(format!(
+                    "line {}: option \"{}\" needs a value",
+                    idx + 1,
+                    line
+                ))
+            }
+        };
+
+        if name == "config" {
+            return Err(format!(
+                "line {}: a config file can't read another",
+                idx + 1
+            ));
+        }
+
+        options.push((idx + 1, name.to_string(), value.to_string()));
+    }
+
+    Ok(options)
+}
+
+impl Config {
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "version" => self.show_version = parse_bool(key, value)?,
+            "self-test" => self.self_test = parse_bool(key, value)?,
+            "config" => self.config_file = Some(value.to_string()),
+
+            "aftertouch" => {
+                self.aftertouch = match value {
+                    "poly" => Some(AftertouchMode::Poly),
+                    "cc" => Some(AftertouchMode::Cc),
+                    "channel" => Some(AftertouchMode::Channel),
+                    _ => {
+                        return Err(format!(
+                            "{} expects poly, cc or channel, got \"{}\"",
+                            key, value
+                        ))
+                    }
+                }
+            }
+
+            "aftertouch-cc" => {
+                self.aftertouch_cc = match value.parse::<U7>() {
+                    Ok(cc @ 0..=119) => cc,
+                    _ => {
+                        return Err(format!(
+                            "{} expects a controller 0-119, got \"{}\"",
+                            key, value
+                        ))
+                    }
+                }
+            }
+
+            "aftertouch-held" => {
+                self.aftertouch_held = match value {
+                    "each" => AftertouchHeld::Each,
+                    "max" => AftertouchHeld::Max,
+                    "latest" => AftertouchHeld::Latest,
+                    "average" => AftertouchHeld::Average,
+                    "sum" => AftertouchHeld::Sum,
+                    _ => {
+                        return Err(format!(
+                            "{} expects each, max, latest, average or sum, got \"{}\"",
+                            key, value
+                        ))
+                    }
+                }
+            }
+
+            "aftertouch-threshold" => {
+                self.aftertouch_threshold = parse_f32(key, value)?.clamp(0.0, 1.0);
+            }
+
+            "button-channel" => {
+                let (buttons, channel) = match value.find('=') {
+                    Some(eq) => (&value[..eq], parse_channel(key, &value[eq + 1..])?),
+                    None => return Err(format!("{} expects BUTTONS=CH, got \"{}\"", key, value)),
+                };
+
+                for button in buttons.split(',') {
+                    let button = button.trim();
+
+                    self.button_channels.retain(|(b, _)| *b != button);
+                    self.button_channels.push((button.to_string(), channel));
+                }
+            }
+
+            "button-note" => {
+                let (button, note) = match value.find('=') {
+                    Some(eq) => (value[..eq].trim(), &value[eq + 1..]),
+                    None => {
+                        return Err(format!(
+                            "{} expects BUTTON=NOTE[:VELOCITY], got \"{}\"",
+                            key, value
+                        ))
+                    }
+                };
+
+                let (note, velocity) = match note.find(':') {
+                    Some(colon) => (&note[..colon], &note[colon + 1..]),
+                    None => (note, "127"),
+                };
+
+                let note = match note.trim().parse::<U7>() {
+                    Ok(note @ 0..=127) => note,
+                    _ => return Err(format!("{} expects notes 0-127, got \"{}\"", key, note)),
+                };
+                let velocity = match velocity.trim().parse::<U7>() {
+                    Ok(velocity @ 1..=127) => velocity,
+                    _ => {
+                        return Err(format!(
+                            "{} expects velocities 1-127, got \"{}\"",
+                            key, velocity
+                        ))
+                    }
+                };
+
+                self.button_notes.retain(|(b, _, _)| *b != button);
+                self.button_notes.push((button.to_string(), note, velocity));
+            }
+
+            "auto-repeat" => {
+                let (buttons, times) = match value.find('=') {
+                    Some(eq) => (&value[..eq], &value[eq + 1..]),
+                    None => {
+                        return Err(format!(
+                            "{} expects BUTTONS=DELAY:INTERVAL, got \"{}\"",
+                            key, value
+                        ))
+                    }
+                };
+
+                let (delay, interval) = match times.find(':') {
+                    Some(colon) => (&times[..colon], &times[colon + 1..]),
+                    None => {
+                        return Err(format!(
+                            "{} expects BUTTONS=DELAY:INTERVAL, got \"{}\"",
+                            key, value
+                        ))
+                    }
+                };
+
+                let repeat = AutoRepeat {
+                    delay: Duration::from_millis(parse_u64(key, delay.trim())?),
+                    interval: match parse_u64(key, interval.trim())? {
+                        0 => return Err(format!("{} can't repeat every 0ms", key)),
+                        ms => Duration::from_millis(ms),
+                    },
+                };
+
+                for name in buttons.split(',').map(str::trim) {
+                    let btn = match osc_button_to_btn_map(name) {
+                        Some(btn) => btn,
+                        None => {
+                            return Err(format!("{}: there's no button called \"{}\"", key, name))
+                        }
+                    };
+
+                    self.auto_repeats.retain(|&(b, _)| b != btn);
+                    self.auto_repeats.push((btn, repeat));
+                }
+            }
+
+            "choke" => {
+                // every --choke is a group of its own
+                let group = match self.choke_groups.iter().flatten().max() {
+                    Some(&last) => last + 1,
+                    None => 0,
+                };
+
+                if group >= 16 {
+                    return Err(format!("too many {} groups", key));
+                }
+
+                for pad in value.split(',') {
+                    match pad.trim().parse::<usize>() {
+                        Ok(pad @ 0..=15) => self.choke_groups[pad] = Some(group),
+                        _ => return Err(format!("{} expects pads 0-15, got \"{}\"", key, pad)),
+                    }
+                }
+            }
+
+            "coalesce" => self.coalesce = parse_bool(key, value)?,
+            "debounce" => self.debounce = Duration::from_millis(parse_u64(key, value)?),
+            "no-midi-batch" => self.midi_batch = !parse_bool(key, value)?,
+            "no-picture" => self.draw_picture = !parse_bool(key, value)?,
+            "no-screen" => self.screen = !parse_bool(key, value)?,
+            "encoder-readout" => self.encoder_readout = parse_bool(key, value)?,
+            "keep-screen" => self.keep_screen = parse_bool(key, value)?,
+            "latency" => self.latency = parse_bool(key, value)?,
+            "learn" => self.learn = parse_bool(key, value)?,
+            "led-follow" => self.led_follow = parse_bool(key, value)?,
+            "led-fade" => self.led_fade = Duration::from_millis(parse_u64(key, value)?),
+            "led-gamma" => {
+                self.led_gamma = parse_f32(key, value)?;
+                if !(MIN_LED_GAMMA..=MAX_LED_GAMMA).contains(&self.led_gamma) {
+                    return Err(format!(
+                        "{} expects {:.1}-{:.1}, got \"{}\"",
+                        key, MIN_LED_GAMMA, MAX_LED_GAMMA, value
+                    ));
+                }
+            }
+            "led-palette" => self.led_palette = parse_bool(key, value)?,
+            "page-octaves" => self.page_octaves = parse_bool(key, value)?,
+            "light-test" => self.light_test = parse_bool(key, value)?,
+            "dark-start" => self.dark_start = parse_bool(key, value)?,
+            "metronome" => self.metronome = parse_bool(key, value)?,
+            "note-grid" => self.note_grid = parse_bool(key, value)?,
+            "erase-as-stop" => self.erase_as_stop = parse_bool(key, value)?,
+            "osc-notes" => self.osc_notes = parse_bool(key, value)?,
+            "osc-pressure" => self.osc_pressure = parse_bool(key, value)?,
+
+            "encoder-fine" => self.encoder_fine = parse_f32(key, value)?.clamp(0.0, 1.0),
+
+            "encoder-format" => {
+                self.encoder_format = match value {
+                    "int" => EncoderFormat::Int,
+                    "float" => EncoderFormat::Float,
+                    "absolute" => EncoderFormat::Absolute,
+                    "direction" => EncoderFormat::Direction,
+                    _ => {
+                        return Err(format!(
+                            "{} expects int, float, absolute or direction, got \"{}\"",
+                            key, value
+                        ))
+                    }
+                }
+            }
+
+            "encoder-mode" => {
+                self.encoder_mode = match value {
+                    "relative" => EncoderMode::Relative,
+                    "wrap" => EncoderMode::Wrap,
+                    "clamp" => EncoderMode::Clamp,
+                    "screen" => EncoderMode::Screen,
+                    "note-base" => EncoderMode::NoteBase,
+                    "list-wrap" => EncoderMode::ListWrap,
+                    "list-clamp" => EncoderMode::ListClamp,
+                    _ => {
+                        return Err(format!(
+                            "{} expects relative, wrap, clamp, screen, note-base, list-wrap or \
+                             list-clamp, got \"{}\"",
+                            key, value
+                        ))
+                    }
+                }
+            }
+
+            "encoder-values" => {
+                self.encoder_values.clear();
+
+                for v in value.split(',') {
+                    match v.trim().parse::<U7>() {
+                        Ok(v @ 0..=127) => self.encoder_values.push(v),
+                        _ => return Err(format!("{} expects values 0-127, got \"{}\"", key, v)),
+                    }
+                }
+            }
+
+            "encoder-midi" => {
+                self.encoder_midi = match value {
+                    "program" => Some(EncoderMidi::Program),
+                    _ => match value.parse::<U7>() {
+                        Ok(cc @ 0..=127) => Some(EncoderMidi::Cc(cc)),
+                        _ => {
+                            return Err(format!(
+                                "{} expects program or a controller 0-127, got \"{}\"",
+                                key, value
+                            ))
+                        }
+                    },
+                }
+            }
+
+            "input" => {
+                self.input = match value {
+                    "hid" => InputSource::Hid,
+                    "keyboard" => InputSource::Keyboard,
+                    _ => {
+                        return Err(format!(
+                            "{} expects hid or keyboard, got \"{}\"",
+                            key, value
+                        ))
+                    }
+                }
+            }
+
+            "idle-color" => {
+                let rgb = parse_color(key, value)?;
+                self.idle_color = Some(HSL::from_rgb(&[
+                    (rgb >> 16) as u8,
+                    (rgb >> 8) as u8,
+                    rgb as u8,
+                ]));
+            }
+
+            "group-color" => self.group_color = Some(parse_color(key, value)?),
+
+            "humanize" => {
+                self.humanize = match value.trim().parse::<u8>() {
+                    Ok(0) => None,
+                    Ok(amount @ 1..=127) => Some(amount),
+                    _ => return Err(format!("{} expects 0-127, got \"{}\"", key, value)),
+                }
+            }
+            "humanize-seed" => self.humanize_seed = Some(parse_u64(key, value)?),
+
+            "idle-brightness" => {
+                self.idle_brightness = parse_f32(key, value)?.clamp(0.0, 1.0);
+            }
+
+            "light-interval" => self.light_interval = Duration::from_millis(parse_u64(key, value)?),
+            "light-refresh" => self.light_refresh = Duration::from_millis(parse_u64(key, value)?),
+
+            // 0 turns it back off
+            "max-hold" => {
+                self.max_hold = match parse_u64(key, value)? {
+                    0 => None,
+                    ms => Some(Duration::from_millis(ms)),
+                }
+            }
+
+            "max-poly" => {
+                self.max_poly = match parse_u64(key, value)? {
+                    0 => None,
+                    n => Some(n as usize),
+                }
+            }
+
+            "min-note" => {
+                self.min_note = match parse_u64(key, value)? {
+                    0 => None,
+                    ms => Some(Duration::from_millis(ms)),
+                }
+            }
+
+            "one-shot" => {
+                self.one_shot = match parse_u64(key, value)? {
+                    0 => None,
+                    ms => Some(Duration::from_millis(ms)),
+                }
+            }
+
+            "mode-button" => {
+                let (name, mode) = match value.find('=') {
+                    Some(eq) => (value[..eq].trim(), value[eq + 1..].trim()),
+                    None => return Err(format!("{} expects BUTTON=MODE, got \"{}\"", key, value)),
+                };
+
+                let btn = match osc_button_to_btn_map(name) {
+                    Some(btn) => btn,
+                    None => return Err(format!("{}: there's no button called \"{}\"", key, name)),
+                };
+
+                let mode = match mode {
+                    "normal" => DriverMode::Normal,
+                    "one-shot" => DriverMode::OneShot,
+                    "latch" => DriverMode::Latch,
+                    _ => {
+                        return Err(format!(
+                            "{} expects normal, one-shot or latch, got \"{}\"",
+                            key, mode
+                        ))
+                    }
+                };
+
+                self.mode_buttons.retain(|&(b, _)| b != btn);
+                self.mode_buttons.push((btn, mode));
+            }
+
+            "modifiers" => {
+                self.modifiers.clear();
+
+                for name in value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                {
+                    match osc_button_to_btn_map(name) {
+                        Some(btn) if self.modifiers.contains(&btn) => {}
+                        Some(btn) => self.modifiers.push(btn),
+                        None => {
+                            return Err(format!("{}: there's no button called \"{}\"", key, name))
+                        }
+                    }
+                }
+
+                // a bit each in a u32
+                if self.modifiers.len() > 32 {
+                    return Err(format!("{} takes at most 32 buttons", key));
+                }
+            }
+
+            "toggle-buttons" => {
+                for name in value.split(',').map(str::trim) {
+                    match osc_button_to_btn_map(name) {
+                        Some(btn) if self.toggle_buttons.contains(&btn) => {}
+                        Some(btn) => self.toggle_buttons.push(btn),
+                        None => {
+                            return Err(format!("{}: there's no button called \"{}\"", key, name))
+                        }
+                    }
+                }
+            }
+
+            "toggle-on-color" => self.toggle_on_color = parse_color(key, value)?,
+            "toggle-off-color" => self.toggle_off_color = parse_color(key, value)?,
+
+            "release-lockout" => {
+                self.release_lockout = Duration::from_millis(parse_u64(key, value)?);
+            }
+
+            "roll-limit" => {
+                self.roll_limit = match parse_u64(key, value)? {
+                    0 => return Err(format!("{} can't be 0", key)),
+                    limit => Some(limit as usize),
+                }
+            }
+
+            "roll-overflow" => {
+                self.roll_overflow = match value {
+                    "queue" => RollOverflow::Queue,
+                    "drop" => RollOverflow::Drop,
+                    _ => return Err(format!("{} expects queue or drop, got \"{}\"", key, value)),
+                }
+            }
+
+            "shared-notes" => {
+                self.shared_notes = match value {
+                    "last" => SharedNotePolicy::Last,
+                    "each" => SharedNotePolicy::Each,
+                    _ => return Err(format!("{} expects last or each, got \"{}\"", key, value)),
+                }
+            }
+
+            "note-range" => {
+                self.note_range = match value {
+                    "clamp" => NoteRangePolicy::Clamp,
+                    "drop" => NoteRangePolicy::Drop,
+                    _ => return Err(format!("{} expects clamp or drop, got \"{}\"", key, value)),
+                }
+            }
+
+            "osc-listen" => self.osc_listen = parse_addr(key, value.trim())?,
+
+            "osc-send" => {
+                for addr in value.split(',') {
+                    let addr = parse_addr(key, addr.trim())?;
+                    self.osc_send.push(addr);
+                }
+            }
+
+            "metronome-notes" => {
+                let notes: Vec<_> = value.split(',').map(|note| note.trim()).collect();
+                if notes.len() != 2 {
+                    return Err(format!("{} expects 2 notes, got {}", key, notes.len()));
+                }
+
+                let mut parsed = [0 as U7; 2];
+                for (n, note) in notes.iter().enumerate() {
+                    parsed[n] = match note.parse::<U7>() {
+                        Ok(note @ 0..=127) => note,
+                        _ => return Err(format!("{} expects notes 0-127, got \"{}\"", key, note)),
+                    };
+                }
+
+                self.metronome_notes = (parsed[0], parsed[1]);
+            }
+
+            "metronome-channel" => self.metronome_channel = parse_channel(key, value)?,
+
+            "midi-name" | "midi-out-port" | "midi-in-port" if value.is_empty() => {
+                return Err(format!("{} expects a name", key))
+            }
+            "midi-name" => self.midi_name = value.to_string(),
+            "midi-out-port" => self.midi_out_port = value.to_string(),
+            "midi-in-port" => self.midi_in_port = value.to_string(),
+
+            "midi-thru" => {
+                self.midi_thru.clear();
+
+                for kind in value.split(',').map(|kind| kind.trim()) {
+                    match kind {
+                        "all" => self.midi_thru.extend_from_slice(&MIDI_THRU_ALL),
+                        "note" => self.midi_thru.push(EventKind::Note),
+                        "cc" => self.midi_thru.push(EventKind::Controller),
+                        "program" => self.midi_thru.push(EventKind::ProgramChange),
+                        "pressure" => self.midi_thru.push(EventKind::Pressure),
+                        "pitchbend" => self.midi_thru.push(EventKind::PitchBend),
+                        "sysex" => self.midi_thru.push(EventKind::SysEx),
+                        "realtime" => self.midi_thru.push(EventKind::Realtime),
+                        _ => return Err(format!("{} expects MIDI types, got \"{}\"", key, kind)),
+                    }
+                }
+            }
+
+            "osc-repeat" => {
+                let (kinds, repeats) = match value.find('=') {
+                    Some(eq) => (&value[..eq], parse_u64(key, &value[eq + 1..])?),
+                    None => return Err(format!("{} expects KINDS=N, got \"{}\"", key, value)),
+                };
+
+                if repeats == 0 || repeats > MAX_OSC_REPEATS {
+                    return Err(format!(
+                        "{} sends each message 1 to {} times, got {}",
+                        key, MAX_OSC_REPEATS, repeats
+                    ));
+                }
+
+                for kind in kinds.split(',') {
+                    let kind = match kind.trim() {
+                        "button" => OscKind::Button,
+                        "encoder" => OscKind::Encoder,
+                        "pressure" => OscKind::Pressure,
+                        "other" => OscKind::Other,
+                        kind => {
+                            return Err(format!(
+                                "{} expects button, encoder, pressure or other, got \"{}\"",
+                                key, kind
+                            ))
+                        }
+                    };
+
+                    self.osc_repeats.retain(|&(k, _)| k != kind);
+                    self.osc_repeats.push((kind, repeats as u32));
+                }
+            }
+
+            "osc-unknown" => {
+                self.osc_unknown = match value {
+                    "ignore" => OscUnknown::Ignore,
+                    "log" => OscUnknown::Log,
+                    "echo" => OscUnknown::Echo,
+                    _ => {
+                        return Err(format!(
+                            "{} expects ignore, log or echo, got \"{}\"",
+                            key, value
+                        ))
+                    }
+                }
+            }
+
+            "pad-channels" => {
+                let channels: Vec<_> = value.split(',').collect();
+
+                match channels.len() {
+                    1 => self.pad_channels = [parse_channel(key, channels[0])?; 16],
+                    16 => {
+                        for (pad, channel) in channels.iter().enumerate() {
+                            self.pad_channels[pad] = parse_channel(key, channel)?;
+                        }
+                    }
+                    n => return Err(format!("{} expects 1 or 16 channels, got {}", key, n)),
+                }
+            }
+
+            "pad-notes" => {
+                self.pad_notes = match value {
+                    "gm" => Some(GM_DRUM_PAD_NOTES),
+                    _ => Some(parse_pad_notes(key, value)?),
+                }
+            }
+
+            "pad-zones" => {
+                let (pad, zones) = match value.find('=') {
+                    Some(eq) => (&value[..eq], &value[eq + 1..]),
+                    None => {
+                        return Err(format!(
+                            "{} expects PAD=VELOCITY:NOTE,..., got \"{}\"",
+                            key, value
+                        ))
+                    }
+                };
+
+                let pad = match pad.trim().parse::<usize>() {
+                    Ok(pad @ 0..=15) => pad,
+                    _ => return Err(format!("{} expects pads 0-15, got \"{}\"", key, pad)),
+                };
+
+                let mut parsed = vec![];
+                for zone in zones.split(',') {
+                    let (from, note) = match zone.find(':') {
+                        Some(colon) => (zone[..colon].trim(), zone[colon + 1..].trim()),
+                        None => {
+                            return Err(format!(
+                                "{} expects VELOCITY:NOTE zones, got \"{}\"",
+                                key, zone
+                            ))
+                        }
+                    };
+
+                    match (from.parse::<U7>(), note.parse::<U7>()) {
+                        (Ok(from @ 0..=127), Ok(note @ 0..=127)) => parsed.push((from, note)),
+                        _ => {
+                            return Err(format!(
+                                "{} expects velocities and notes 0-127, got \"{}\"",
+                                key, zone
+                            ))
+                        }
+                    }
+                }
+
+                // zone_note() looks for the last band a velocity reaches
+                parsed.sort_by_key(|&(from, _)| from);
+                self.pad_zones[pad] = parsed;
+            }
+
+            "pad-light" => {
+                self.pad_light = match value {
+                    "follow" => PadLight::Follow,
+                    "peak" => PadLight::Peak,
+                    _ => return Err(format!("{} expects follow or peak, got \"{}\"", key, value)),
+                }
+            }
+
+            "pressure" => {
+                let (shape, param) = match value.find(':') {
+                    Some(colon) => (&value[..colon], Some(&value[colon + 1..])),
+                    None => (value, None),
+                };
+                let param = param.map(|param| param.trim().parse::<f32>());
+
+                self.pressure = Some(match (shape, param) {
+                    ("linear", None) => PressureShape::Linear,
+                    ("exp", Some(Ok(power))) if power > 0.0 => PressureShape::Exponential(power),
+                    ("const", Some(Ok(pressure))) if (0.0..=1.0).contains(&pressure) => {
+                        PressureShape::Constant(pressure)
+                    }
+                    _ => {
+                        return Err(format!(
+                            "{} expects linear, exp:POWER (above 0) or const:PRESSURE (0.0-1.0), \
+                             got \"{}\"",
+                            key, value
+                        ))
+                    }
+                });
+            }
+
+            "quantize" => {
+                self.quantize = match Subdivision::from_name(value) {
+                    Some(subdivision) => Some(subdivision),
+                    None => {
+                        return Err(format!(
+                            "{} expects 1/4, 1/8, 1/16 or 1/32, got \"{}\"",
+                            key, value
+                        ))
+                    }
+                }
+            }
+
+            "smoothing" => self.smoothing = parse_f32(key, value)?.clamp(0.0, 0.99),
+
+            "shift-layer" => self.shift_layer = Some(parse_pad_notes(key, value)?),
+
+            "sustain-button" => match osc_button_to_btn_map(value) {
+                Some(btn) => self.sustain_button = Some(btn),
+                None => return Err(format!("{}: there's no button called \"{}\"", key, value)),
+            },
+
+            "theme" => {
+                let (name, colors) = match value.find('=') {
+                    Some(eq) => (&value[..eq], &value[eq + 1..]),
+                    None => return Err(format!("{} expects NAME=COLORS, got \"{}\"", key, value)),
+                };
+
+                let mut parsed = vec![];
+                for color in colors.split(',') {
+                    let rgb = parse_color(key, color)?;
+                    parsed.push(HSL::from_rgb(&[
+                        (rgb >> 16) as u8,
+                        (rgb >> 8) as u8,
+                        rgb as u8,
+                    ]));
+                }
+
+                let palette = match parsed.len() {
+                    2 => theme_gradient(parsed[0], parsed[1]),
+                    16 => {
+                        let mut palette = [parsed[0]; 16];
+                        palette.copy_from_slice(&parsed);
+                        palette
+                    }
+                    n => return Err(format!("{} expects 2 or 16 colours, got {}", key, n)),
+                };
+
+                // a later --theme of the same name wins
+                self.themes.retain(|(n, _)| *n != name);
+                self.themes.push((name.to_string(), palette));
+            }
+
+            "velocity-scale" => {
+                self.velocity_scale = parse_f32(key, value)?;
+                if self.velocity_scale < 0.0 {
+                    return Err(format!("{} can't be negative, got \"{}\"", key, value));
+                }
+            }
+
+            "velocity-table" => self.velocity_table = Some(value.to_string()),
+
+            // 0 turns it back off
+            "watchdog" => {
+                self.watchdog = match parse_u64(key, value)? {
+                    0 => None,
+                    ms => Some(Duration::from_millis(ms)),
+                }
+            }
+
+            "watchdog-exit" => self.watchdog_exit = parse_bool(key, value)?,
+
+            _ => return Err(unknown_option(key)),
+        }
+
+        Ok(())
+    }
+
+    // how many times each outgoing message of the kind is sent, once unless --osc-repeat says
+    pub fn osc_repeat(&self, kind: OscKind) -> u32 {
+        self.osc_repeats
+            .iter()
+            .find(|&&(k, _)| k == kind)
+            .map_or(1, |&(_, repeats)| repeats)
+    }
+
+    // buttons are named as in their /maschine/button path
+    pub fn button_channel(&self, button: &str) -> Channel {
+        self.button_channels
+            .iter()
+            .find(|(b, _)| b == button)
+            .map_or(Ch1, |&(_, channel)| channel)
+    }
+
+    pub fn auto_repeat(&self, btn: MaschineButton) -> Option<AutoRepeat> {
+        self.auto_repeats
+            .iter()
+            .find(|&&(b, _)| b == btn)
+            .map(|&(_, repeat)| repeat)
+    }
+
+    pub fn button_action(&self, button: &str) -> Option<ButtonAction> {
+        self.button_notes
+            .iter()
+            .find(|(b, _, _)| b == button)
+            .map(|&(_, note, velocity)| ButtonAction::Note {
+                note,
+                channel: self.button_channel(button),
+                velocity,
+            })
+    }
+
+    // with --pad-zones, the note of the highest band the velocity reaches. below the lowest
+    // band the pad plays its usual note
+    pub fn zone_note(&self, pad_idx: usize, velocity: U7) -> Option<U7> {
+        self.pad_zones[pad_idx]
+            .iter()
+            .rev()
+            .find(|&&(from, _)| velocity >= from)
+            .map(|&(_, note)| note)
+    }
+
+    // what the pads start out in, and go back to on /maschine/reset
+    pub fn start_mode(&self) -> DriverMode {
+        if self.one_shot.is_some() {
+            DriverMode::OneShot
+        } else {
+            DriverMode::Normal
+        }
+    }
+
+    pub fn osc_destinations(&self) -> Vec<SocketAddr> {
+        if self.osc_send.is_empty() {
+            vec![SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::LOCALHOST,
+                OSC_SEND_PORT,
+            ))]
+        } else {
+            self.osc_send.clone()
+        }
+    }
+
+    // what can't be seen from any one option on its own. each option has already been checked
+    // against its own ranges by set()
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut problems = vec![];
+
+        for (button, _) in &self.button_channels {
+            if osc_button_to_btn_map(button).is_none() {
+                problems.push(Problem::Error(format!(
+                    "--button-channel: there's no button called \"{}\"",
+                    button
+                )));
+            }
+        }
+
+        for (i, (button, note, _)) in self.button_notes.iter().enumerate() {
+            if osc_button_to_btn_map(button).is_none() {
+                problems.push(Problem::Error(format!(
+                    "--button-note: there's no button called \"{}\"",
+                    button
+                )));
+            }
+
+            let channel = self.button_channel(button);
+            let shared = self.button_notes[..i]
+                .iter()
+                .find(|(b, n, _)| n == note && self.button_channel(b) == channel);
+            if let Some((other, _, _)) = shared {
+                problems.push(Problem::Warning(format!(
+                    "--button-note: {} and {} both play note {} on channel {}, letting go of \
+                     one ends the other",
+                    other,
+                    button,
+                    note,
+                    channel as u8 + 1
+                )));
+            }
+
+            // only fixed pad notes can be known ahead of time
+            if let Some(ref pad_notes) = self.pad_notes {
+                let pad = (0..16)
+                    .find(|&pad| pad_notes[pad] == *note && self.pad_channels[pad] == channel);
+                if let Some(pad) = pad {
+                    problems.push(Problem::Warning(format!(
+                        "--button-note: {} plays note {} on channel {}, which pad {} plays too",
+                        button,
+                        note,
+                        channel as u8 + 1,
+                        pad
+                    )));
+                }
+            }
+        }
+
+        // every button without a --button-channel sends on channel 1
+        let button_channels: Vec<Channel> = self
+            .button_channels
+            .iter()
+            .map(|&(_, channel)| channel)
+            .chain(Some(Ch1))
+            .collect();
+
+        if self.aftertouch == Some(AftertouchMode::Cc)
+            && RPN_CONTROLLERS.contains(&self.aftertouch_cc)
+        {
+            let pad = (0..16).find(|&pad| button_channels.contains(&self.pad_channels[pad]));
+            if let Some(pad) = pad {
+                problems.push(Problem::Error(format!(
+                    "--aftertouch-cc {} is part of the button RPNs, and pad {} shares channel {} \
+                     with the buttons",
+                    self.aftertouch_cc,
+                    pad,
+                    self.pad_channels[pad] as u8 + 1
+                )));
+            }
+        }
+
+        for group in 0..16 {
+            let pads = self
+                .choke_groups
+                .iter()
+                .filter(|&&g| g == Some(group))
+                .count();
+            if pads == 1 {
+                problems.push(Problem::Warning(format!(
+                    "--choke: group {} has only one pad, so there's nothing for it to cut off",
+                    group + 1
+                )));
+            }
+        }
+
+        for (pad, zones) in self.pad_zones.iter().enumerate() {
+            for pair in zones.windows(2) {
+                if pair[0].0 == pair[1].0 {
+                    problems.push(Problem::Warning(format!(
+                        "--pad-zones: pad {} has two zones from velocity {}, only note {} will \
+                         play",
+                        pad, pair[1].0, pair[1].1
+                    )));
+                }
+            }
+        }
+
+        if self.pressure.is_some() && self.velocity_table.is_some() {
+            problems.push(Problem::Warning(
+                "--pressure is ignored, --velocity-table takes over from it".to_string(),
+            ));
+        }
+
+        let list_mode = matches!(
+            self.encoder_mode,
+            EncoderMode::ListWrap | EncoderMode::ListClamp
+        );
+        if list_mode && self.encoder_values.is_empty() {
+            problems.push(Problem::Error(
+                "--encoder-mode list-wrap and list-clamp need --encoder-values".to_string(),
+            ));
+        }
+        if !list_mode && (!self.encoder_values.is_empty() || self.encoder_midi.is_some()) {
+            problems.push(Problem::Warning(
+                "--encoder-values and --encoder-midi only do anything in the list modes"
+                    .to_string(),
+            ));
+        }
+
+        if self.shift_layer.is_some() && !self.modifiers.contains(&MaschineButton::Shift) {
+            problems.push(Problem::Warning(
+                "--shift-layer needs shift in --modifiers, it will never play".to_string(),
+            ));
+        }
+
+        // with the note base the pads can play anything, so only fixed pad notes can rule it out
+        if self.metronome {
+            let (beat, accent) = self.metronome_notes;
+            let clashes = (0..16).any(|pad| {
+                self.pad_channels[pad] == self.metronome_channel
+                    && self
+                        .pad_notes
+                        .is_none_or(|notes| notes[pad] == beat || notes[pad] == accent)
+            });
+
+            if clashes {
+                problems.push(Problem::Warning(format!(
+                    "--metronome-channel {} is a pad channel, the clicks could cut off pad notes",
+                    self.metronome_channel as u8 + 1
+                )));
+            }
+        }
+
+        problems
+    }
+
+    // the environment's MASCHINE_ variables first, then the command line, so that an option
+    // given on both goes by the command line
+    pub fn from_env_and_args(vars: &[(String, String)], args: &[String]) -> Result<Config, String> {
+        let mut config = Config::default();
+        let mut positional = 0;
+        let mut options = vec![];
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let opt = match arg.strip_prefix("--") {
+                Some(opt) => opt,
+                None => {
+                    match positional {
+                        0 => config.device = Some(arg.clone()),
+
+                        // historically any extra argument skipped the startup picture
+                        _ => config.draw_picture = false,
+                    }
+
+                    positional += 1;
+                    continue;
+                }
+            };
+
+            if let Some(eq) = opt.find('=') {
+                options.push((&opt[..eq], &opt[eq + 1..]));
+            } else if FLAGS.contains(&opt) {
+                options.push((opt, "on"));
+            } else {
+                match args.next() {
+                    Some(value) => options.push((opt, value)),
+                    None => return Err(format!("option \"--{}\" needs a value", opt)),
+                }
+            }
+        }
+
+        let env_options: Vec<_> = vars
+            .iter()
+            .filter_map(|(name, value)| {
+                let key = name.strip_prefix(ENV_PREFIX)?;
+                Some((name, key.to_lowercase().replace('_', "-"), value))
+            })
+            .collect();
+
+        // the command line's --config if it has one, otherwise the environment's
+        let config_file = options
+            .iter()
+            .rev()
+            .find(|&&(opt, _)| opt == "config")
+            .map(|&(_, path)| path)
+            .or_else(|| {
+                env_options
+                    .iter()
+                    .find(|(_, key, _)| key == "config")
+                    .map(|&(_, _, path)| path.as_str())
+            });
+
+        let file_options = match config_file {
+            Some(path) => {
+                let text = fs::read_to_string(path)
+                    .map_err(|err| format!("couldn't read {}: {}", path, err))?;
+                parse_config_file(&text).map_err(|err| format!("{}: {}", path, err))?
+            }
+            None => vec![],
+        };
+
+        for (name, key, value) in env_options {
+            // options that can be repeated would otherwise add up
+            if options.iter().any(|&(opt, _)| opt == key)
+                || file_options.iter().any(|(_, opt, _)| *opt == key)
+            {
+                continue;
+            }
+
+            if key == "device" {
+                if config.device.is_none() {
+                    config.device = Some(value.clone());
+                }
+                continue;
+            }
+
+            // other programs' MASCHINE_ variables can share the environment
+            match config.set(&key, value) {
+                Err(ref err) if *err == unknown_option(&key) => {
+                    println!(" :: ignoring {}, there's no --{} option", name, key)
+                }
+                result => result.map_err(|err| format!("{} (from {})", err, name))?,
+            }
+        }
+
+        for (line, opt, value) in &file_options {
+            if options.iter().any(|&(name, _)| name == opt) {
+                continue;
+            }
+
+            config
+                .set(opt, value)
+                .map_err(|err| format!("{} (from {} line {})", err, config_file.unwrap(), line))?;
+        }
+
+        for (opt, value) in options {
+            config.set(opt, value)?;
+        }
+
+        Ok(config)
+    }
+}
@@ -0,0 +1,197 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+use std::os::unix::io::RawFd;
+use std::process;
+
+extern crate nix;
+use nix::sys::termios;
+use nix::unistd;
+
+use base::{Maschine, MaschineButton, MaschineEvent, MaschineHandler};
+use devices::mk2::mikro::SCREEN_BUF_SIZE;
+
+const STDIN: RawFd = 0;
+
+// keys can't be held, so every key is a full hit at this pressure, then released
+const KEY_PRESSURE: f32 = 0.8;
+
+// laid out like the pads, top row first
+const PAD_KEYS: &[u8; 16] = b"1234qwerasdfzxcv";
+
+const GROUP_KEYS: &[u8; 8] = b"ABCDEFGH";
+const GROUP_BUTTONS: [MaschineButton; 8] = [
+    MaschineButton::GroupA,
+    MaschineButton::GroupB,
+    MaschineButton::GroupC,
+    MaschineButton::GroupD,
+    MaschineButton::GroupE,
+    MaschineButton::GroupF,
+    MaschineButton::GroupG,
+    MaschineButton::GroupH,
+];
+
+// the events a single keystroke stands for
+pub fn key_events(key: u8) -> Vec<MaschineEvent> {
+    if let Some(pad) = PAD_KEYS.iter().position(|&k| k == key) {
+        return vec![
+            MaschineEvent::PadPressed(pad, KEY_PRESSURE),
+            MaschineEvent::PadReleased(pad),
+        ];
+    }
+
+    let btn = match key {
+        b'[' => return vec![MaschineEvent::EncoderStep(0, -1)],
+        b']' => return vec![MaschineEvent::EncoderStep(0, 1)],
+
+        b' ' => MaschineButton::Play,
+        b'.' => MaschineButton::Rec,
+        b'\r' | b'\n' => MaschineButton::Enter,
+        b't' => MaschineButton::Tempo,
+        b'\\' => MaschineButton::Nav,
+
+        _ => match GROUP_KEYS.iter().position(|&k| k == key) {
+            Some(group) => GROUP_BUTTONS[group],
+            None => return vec![],
+        },
+    };
+
+    vec![
+        MaschineEvent::ButtonDown(btn, 1),
+        MaschineEvent::ButtonUp(btn, 0),
+    ]
+}
+
+// stands in for a device, reading keystrokes from the terminal. there's nothing to light, so
+// lights and the screen go nowhere
+pub struct Keyboard {
+    saved_termios: termios::Termios,
+
+    screen: [u8; SCREEN_BUF_SIZE],
+    midi_note_base: u8,
+}
+
+impl Keyboard {
+    pub fn new() -> Result<Self, String> {
+        let saved_termios = termios::tcgetattr(STDIN)
+            .map_err(|err| format!("stdin isn't a terminal: {}", err.errno().desc()))?;
+
+        // keys arrive one at a time and unechoed. ^C is read as a key too, so that the
+        // terminal gets put back before exiting
+        let mut raw = saved_termios;
+        raw.c_lflag
+            .remove(termios::ICANON | termios::ECHO | termios::ISIG);
+        raw.c_cc[termios::VMIN] = 1;
+        raw.c_cc[termios::VTIME] = 0;
+
+        termios::tcsetattr(STDIN, termios::TCSANOW, &raw)
+            .map_err(|err| format!("couldn't set up the terminal: {}", err.errno().desc()))?;
+
+        println!(" :: pads are 1234/qwer/asdf/zxcv, groups A-H, encoder [ ] and \\");
+        println!(" :: space plays, . records, t taps tempo, ^C quits");
+
+        Ok(Keyboard {
+            saved_termios,
+
+            screen: [0u8; SCREEN_BUF_SIZE],
+            midi_note_base: 48,
+        })
+    }
+
+    fn restore_terminal(&self) {
+        let _ = termios::tcsetattr(STDIN, termios::TCSANOW, &self.saved_termios);
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        self.restore_terminal();
+    }
+}
+
+impl Maschine for Keyboard {
+    fn get_fd(&self) -> RawFd {
+        STDIN
+    }
+
+    fn get_pad_pressure(&self, pad_idx: usize) -> Result<f32, ()> {
+        match pad_idx {
+            0..=15 => Ok(0.0),
+            _ => Err(()),
+        }
+    }
+
+    fn get_midi_note_base(&self) -> u8 {
+        self.midi_note_base
+    }
+
+    fn set_midi_note_base(&mut self, base: u8) {
+        self.midi_note_base = base;
+    }
+
+    fn set_pad_light(&mut self, _: usize, _: u32, _: f32) {}
+    fn set_button_light(&mut self, _: MaschineButton, _: u32, _: f32) {}
+
+    fn readable(&mut self, handler: &mut dyn MaschineHandler) {
+        let mut buf = [0u8; 32];
+
+        let nbytes = match unistd::read(STDIN, &mut buf) {
+            Err(err) => panic!("read failed: {}", err),
+            Ok(nbytes) => nbytes,
+        };
+
+        for &key in &buf[..nbytes] {
+            // ^C, ^D
+            if key == 0x03 || key == 0x04 {
+                self.restore_terminal();
+                process::exit(0);
+            }
+
+            for ev in key_events(key) {
+                ev.dispatch(self, handler);
+            }
+        }
+    }
+
+    fn get_screen(&self) -> &[u8] {
+        &self.screen
+    }
+
+    fn get_screen_mut(&mut self) -> &mut [u8] {
+        &mut self.screen
+    }
+
+    fn send_screen(&mut self) {}
+
+    fn force_full_redraw(&mut self) {}
+
+    fn clear_screen(&mut self) {
+        self.screen = [0u8; SCREEN_BUF_SIZE];
+    }
+
+    fn clear_lights(&mut self) {}
+    fn write_lights(&mut self) {}
+
+    fn lights_dirty(&self) -> bool {
+        false
+    }
+
+    fn connected(&self) -> bool {
+        true
+    }
+    fn write_screen(&mut self) {}
+}
@@ -15,17 +15,23 @@
 //  License along with this program.  If not, see
 //  <http://www.gnu.org/licenses/>.
 
+use std::cell::Cell;
 use std::fs::File;
-use std::mem::transmute;
 use std::os::unix::io;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
 
 extern crate nix;
+use nix::errno::Errno;
 use nix::unistd;
 
 extern crate hex;
 extern crate png;
 
-use base::{Maschine, MaschineButton, MaschineHandler, MaschinePad, MaschinePadStateTransition};
+use base::{
+    Clock, LightFrame, Maschine, MaschineButton, MaschineEvent, MaschineHandler, MaschinePad,
+    MaschinePadStateTransition, SystemClock,
+};
 
 const BUTTON_REPORT_TO_MIKROBUTTONS_MAP: [[Option<MaschineButton>; 8]; 23] = [
     [
@@ -49,6 +55,7 @@ const BUTTON_REPORT_TO_MIKROBUTTONS_MAP: [[Option<MaschineButton>; 8]; 23] = [
         Some(MaschineButton::Control),
     ],
     [
+        // pushing down on the master encoder
         Some(MaschineButton::Nav),
         Some(MaschineButton::Noterepeat),
         Some(MaschineButton::Enter),
@@ -260,6 +267,75 @@ const BUTTON_REPORT_TO_MIKROBUTTONS_MAP: [[Option<MaschineButton>; 8]; 23] = [
     ],
 ];
 
+// the screen is drawn from a 256x64 image, packed at one bit per pixel
+pub const SCREEN_BUF_SIZE: usize = 256 * 64 / 8;
+
+// number of single-byte screen reports sent for a full redraw
+const SCREEN_REPORTS: usize = 4097;
+
+// the screen's pixels are only on or off, so it's dimmed by leaving lit pixels out: at level n
+// of 16, those whose place in each 4x4 block comes before n here are left on
+const SCREEN_DITHER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+const SCREEN_LEVELS: u8 = 16;
+
+// the pixels of screen byte `idx` left on at `level`
+fn screen_dither_mask(idx: usize, level: u8) -> u8 {
+    let row = &SCREEN_DITHER[(idx / 32) % 4];
+    let x = (idx % 32) * 8;
+
+    (0..8)
+        .filter(|&bit| row[(x + bit) % 4] < level)
+        .fold(0, |mask, bit| mask | (0x80 >> bit))
+}
+
+// how pad colours are put on the LEDs: as asked for, 7 bits a channel, or snapped to the nearest
+// entry of LED_PALETTE so that the same few colours always look the same
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LedColors {
+    Rgb,
+    Palette,
+}
+
+pub const LED_PALETTE: [u32; 17] = [
+    0xFF0000, // red
+    0xFF4000, // orange
+    0xFF8000, // light orange
+    0xFFC000, // warm yellow
+    0xFFFF00, // yellow
+    0x80FF00, // lime
+    0x00FF00, // green
+    0x00FF80, // mint
+    0x00FFFF, // cyan
+    0x0080FF, // turquoise
+    0x0000FF, // blue
+    0x4000FF, // plum
+    0x8000FF, // violet
+    0xFF00FF, // purple
+    0xFF0080, // magenta
+    0xFF0040, // fuchsia
+    0xFFFFFF, // white
+];
+
+// squared distance in plain RGB, ties go to the earlier entry
+pub fn nearest_palette_color(color: u32) -> u32 {
+    let channel = |c: u32, shift: u32| ((c >> shift) & 0xFF) as i32;
+    let distance = |entry: u32| {
+        [16, 8, 0]
+            .iter()
+            .map(|&shift| (channel(color, shift) - channel(entry, shift)).pow(2))
+            .sum::<i32>()
+    };
+
+    let mut best = LED_PALETTE[0];
+    for &entry in LED_PALETTE[1..].iter() {
+        if distance(entry) < distance(best) {
+            best = entry;
+        }
+    }
+
+    best
+}
+
 #[allow(dead_code)]
 struct ButtonReport {
     pub buttons: u32,
@@ -272,13 +348,69 @@ pub struct Mikro {
     light_buf2: [u8; 32],
     light_buf3: [u8; 57],
 
+    screen: [u8; SCREEN_BUF_SIZE],
+
+    // what the device is currently showing, so that only changed bytes get sent. None when we
+    // can't know, e.g. right after opening the device
+    sent_screen: Option<[u8; SCREEN_BUF_SIZE]>,
+
+    // whether the light reports differ from what was last written
+    lights_dirty: bool,
+
+    // with --no-screen nothing is ever sent to the screen, the buffer is only drawn into
+    screen_enabled: bool,
+    // out of SCREEN_LEVELS, applied to the buffer as it's sent
+    screen_level: u8,
+
+    led_colors: LedColors,
+
+    // applied to each pad LED's level, as they don't look as bright as they're driven
+    gamma: f32,
+
+    // what the lights were asked to show, which light_buf can't be turned back into
+    lights: LightFrame,
+
+    // how long a light takes to go from off to fully on, or back. brightnesses are eased
+    // towards what they were set to on each write_lights, 0 sets them straight away
+    fade: Duration,
+    shown_pads: [f32; 16],
+    shown_buttons: Vec<(MaschineButton, f32)>,
+    // when the fade last took a step, while there's a light still getting there
+    faded_at: Option<SystemTime>,
+    clock: Rc<dyn Clock>,
+
+    // hidraw fails reads and writes with EIO once the device is unplugged, ENODEV is there to
+    // be safe. writes only need &self, hence the Cell
+    connected: Cell<bool>,
+
+    decoder: ReportDecoder,
+
+    midi_note_base: u8,
+}
+
+// turns raw input reports into events, keeping the button and pad state needed to tell what
+// changed from one report to the next
+pub struct ReportDecoder {
     pads: [MaschinePad; 16],
     buttons: [u8; 24],
+}
 
-    midi_note_base: u8,
+impl Default for ReportDecoder {
+    fn default() -> Self {
+        ReportDecoder::new()
+    }
 }
 
-impl Mikro {
+impl ReportDecoder {
+    pub fn new() -> Self {
+        ReportDecoder {
+            pads: ReportDecoder::sixteen_maschine_pads(),
+            buttons: [
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10,
+            ],
+        }
+    }
+
     fn sixteen_maschine_pads() -> [MaschinePad; 16] {
         [
             MaschinePad::default(),
@@ -300,28 +432,24 @@ impl Mikro {
         ]
     }
 
-    pub fn new(dev: io::RawFd) -> Self {
-        let mut _self = Mikro {
-            dev: dev,
-            light_buf: [0u8; 49],
-            light_buf2: [0u8; 32],
-            light_buf3: [0u8; 57],
+    // decodes a whole report, report id included. unknown reports give None
+    pub fn decode(&mut self, report: &[u8]) -> Option<Vec<MaschineEvent>> {
+        let mut events = vec![];
 
-            pads: Mikro::sixteen_maschine_pads(),
-            buttons: [
-                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10,
-            ],
-
-            midi_note_base: 48,
-        };
+        match report.first() {
+            Some(&0x01) => self.decode_buttons(&report[1..], &mut events),
+            Some(&0x20) => self.decode_pads(&report[1..], &mut events),
+            _ => return None,
+        }
 
-        _self.light_buf[0] = 0x80;
-        _self.light_buf2[0] = 0x82;
-        _self.light_buf3[0] = 0x81;
-        return _self;
+        Some(events)
     }
 
-    fn read_buttons(&mut self, handler: &mut dyn MaschineHandler, buf: &[u8]) {
+    fn decode_buttons(&mut self, buf: &[u8], events: &mut Vec<MaschineEvent>) {
+        if buf.len() < 24 {
+            return;
+        }
+
         for (idx, &byte) in buf[0..23].iter().enumerate() {
             let mut diff = (byte ^ self.buttons[idx]) as u32;
 
@@ -333,9 +461,9 @@ impl Mikro {
 
                 if (byte & (1 << (off - 1))) != 0 {
                     //println!("{}", byte);
-                    handler.button_down(self, btn, byte);
+                    events.push(MaschineEvent::ButtonDown(btn, byte));
                 } else {
-                    handler.button_up(self, btn, byte);
+                    events.push(MaschineEvent::ButtonUp(btn, byte));
                 }
                 diff >>= off;
             }
@@ -351,26 +479,33 @@ impl Mikro {
         }
 
         if ((self.buttons[23] + 1) & 0xF) == buf[23] {
-            handler.encoder_step(self, 0, 1);
+            events.push(MaschineEvent::EncoderStep(0, 1));
         } else {
-            handler.encoder_step(self, 0, -1);
+            events.push(MaschineEvent::EncoderStep(0, -1));
         }
 
         self.buttons[23] = buf[23];
     }
 
-    fn read_pads(&mut self, handler: &mut dyn MaschineHandler, buf: &[u8]) {
-        let pads: &[u16] = unsafe { transmute(buf) };
+    fn decode_pads(&mut self, buf: &[u8], events: &mut Vec<MaschineEvent>) {
+        if buf.len() < 32 {
+            return;
+        }
 
         for i in 0..16 {
-            let pressure = ((pads[i] & 0xFFF) as f32) / 4095.0;
+            let raw = u16::from_le_bytes([buf[i * 2], buf[i * 2 + 1]]);
+            let pressure = ((raw & 0xFFF) as f32) / 4095.0;
 
             match self.pads[i].pressure_val(pressure) {
-                MaschinePadStateTransition::Pressed => handler.pad_pressed(self, i, pressure),
+                MaschinePadStateTransition::Pressed => {
+                    events.push(MaschineEvent::PadPressed(i, pressure))
+                }
 
-                MaschinePadStateTransition::Aftertouch => handler.pad_aftertouch(self, i, pressure),
+                MaschinePadStateTransition::Aftertouch => {
+                    events.push(MaschineEvent::PadAftertouch(i, pressure))
+                }
 
-                MaschinePadStateTransition::Released => handler.pad_released(self, i),
+                MaschinePadStateTransition::Released => events.push(MaschineEvent::PadReleased(i)),
 
                 _ => {}
             }
@@ -378,12 +513,262 @@ impl Mikro {
     }
 }
 
-fn set_rgb_light(rgb: &mut [u8], color: u32, brightness: f32) {
-    let brightness = brightness * 0.5;
+impl Mikro {
+    pub fn new(dev: io::RawFd) -> Self {
+        let mut _self = Mikro {
+            dev: dev,
+            light_buf: [0u8; 49],
+            light_buf2: [0u8; 32],
+            light_buf3: [0u8; 57],
+
+            screen: [0u8; SCREEN_BUF_SIZE],
+            sent_screen: None,
+            lights_dirty: true,
+            screen_enabled: true,
+            screen_level: SCREEN_LEVELS,
+            led_colors: LedColors::Rgb,
+            gamma: 1.0,
+            lights: LightFrame::default(),
+            fade: Duration::ZERO,
+            shown_pads: [0.0; 16],
+            shown_buttons: vec![],
+            faded_at: None,
+            clock: Rc::new(SystemClock),
+            connected: Cell::new(true),
+
+            decoder: ReportDecoder::new(),
+
+            midi_note_base: 48,
+        };
+
+        _self.light_buf[0] = 0x80;
+        _self.light_buf2[0] = 0x82;
+        _self.light_buf3[0] = 0x81;
+        return _self;
+    }
+
+    pub fn disable_screen(&mut self) {
+        self.screen_enabled = false;
+    }
+
+    // where fades take their time from
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn set_led_colors(&mut self, colors: LedColors) {
+        self.led_colors = colors;
+    }
+
+    pub fn led_colors(&self) -> LedColors {
+        self.led_colors
+    }
+
+    fn show_pad(&mut self, pad: usize, brightness: f32) {
+        let offset = 1 + (pad * 3);
+        let rgb = &mut self.light_buf[offset..(offset + 3)];
+        let was = [rgb[0], rgb[1], rgb[2]];
+
+        let color = match self.led_colors {
+            LedColors::Rgb => self.lights.pads[pad].0,
+            LedColors::Palette => nearest_palette_color(self.lights.pads[pad].0),
+        };
+
+        set_rgb_light(rgb, color, brightness, self.gamma);
+        self.lights_dirty |= *rgb != was;
+        self.shown_pads[pad] = brightness;
+    }
+
+    fn show_button(&mut self, btn: MaschineButton, brightness: f32) {
+        let light = match button_light_idx(btn) {
+            Some((idx, 0)) => &mut self.light_buf2[idx],
+            Some((_, idx2)) => &mut self.light_buf3[idx2],
+            None => return,
+        };
+
+        self.lights_dirty |= *light != brightness as u8;
+        *light = brightness as u8;
+
+        self.shown_buttons.retain(|&(b, _)| b != btn);
+        self.shown_buttons.push((btn, brightness));
+    }
+
+    fn shown_button(&self, btn: MaschineButton) -> f32 {
+        self.shown_buttons
+            .iter()
+            .find(|&&(b, _)| b == btn)
+            .map_or(0.0, |&(_, brightness)| brightness)
+    }
+
+    fn fading(&self) -> bool {
+        let pads = (0..16).any(|pad| self.shown_pads[pad] != self.lights.pads[pad].1);
+        let buttons = self
+            .lights
+            .buttons
+            .iter()
+            .any(|&(btn, _, brightness)| self.shown_button(btn) != brightness);
+
+        pads || buttons
+    }
+
+    // a fade starts from where the light is now rather than from the last step, or one set
+    // after a quiet spell would jump straight there
+    fn start_fade(&mut self) {
+        if self.faded_at.is_none() {
+            self.faded_at = Some(self.clock.now());
+        }
+        self.lights_dirty = true;
+    }
+
+    // moves every light that isn't there yet towards what it was set to, as far as the time
+    // since the last step allows
+    fn step_fades(&mut self) {
+        let now = self.clock.now();
+        let since = match self.faded_at {
+            Some(at) => now.duration_since(at).unwrap_or_default(),
+            None => return,
+        };
+        // the fade may have been turned off partway through
+        let step = if self.fade == Duration::ZERO {
+            f32::INFINITY
+        } else {
+            since.as_secs_f32() / self.fade.as_secs_f32()
+        };
+
+        for pad in 0..16 {
+            let shown = approach(self.shown_pads[pad], self.lights.pads[pad].1, step);
+            self.show_pad(pad, shown);
+        }
+
+        // button lights go up to 127 rather than 1, and take as long to get there
+        for (btn, _, brightness) in self.lights.buttons.clone() {
+            let shown = approach(self.shown_button(btn), brightness, step * 127.0);
+            self.show_button(btn, shown);
+        }
+
+        self.faded_at = if self.fading() { Some(now) } else { None };
+    }
+
+    // every report carries a single framebuffer byte, addressed by its column in [1] and its
+    // row in [3], 32 bytes to a row
+    fn send_screen_byte(&self, idx: usize, byte: u8) {
+        let mut screen_buf = [0u8; 1 + 8 + 512];
+        screen_buf[0] = 0xE0;
+        screen_buf[1] = (idx % 32) as u8;
+        screen_buf[3] = (idx / 32) as u8;
+        screen_buf[5] = 0x08;
+        screen_buf[7] = 0x20;
+        screen_buf[9] = byte;
+
+        self.write_report(&screen_buf);
+    }
+
+    // once the device is gone there's no one to write to, and the event loop finds out from
+    // connected() rather than a panic
+    fn write_report(&self, report: &[u8]) {
+        if !self.connected.get() {
+            return;
+        }
+
+        match unistd::write(self.dev, report) {
+            Err(nix::Error::Sys(Errno::EIO)) | Err(nix::Error::Sys(Errno::ENODEV)) => {
+                self.connected.set(false)
+            }
+            Err(err) => panic!("write failed: {}", err),
+            Ok(_) => {}
+        }
+    }
+}
+
+fn approach(from: f32, to: f32, step: f32) -> f32 {
+    if (to - from).abs() <= step {
+        to
+    } else if to > from {
+        from + step
+    } else {
+        from - step
+    }
+}
+
+// where a button's light is: an index into light_buf2, or 0 there and one into light_buf3
+fn button_light_idx(btn: MaschineButton) -> Option<(usize, usize)> {
+    let mut idx = 0;
+    let mut idx2 = 0;
+    match btn {
+        MaschineButton::F8 => idx = 1,
+        MaschineButton::F7 => idx = 2,
+        MaschineButton::F6 => idx = 3,
+        MaschineButton::F5 => idx = 4,
+        MaschineButton::F4 => idx = 5,
+        MaschineButton::F3 => idx = 6,
+        MaschineButton::F2 => idx = 7,
+        MaschineButton::F1 => idx = 8,
+
+        MaschineButton::Auto => idx = 9,
+        MaschineButton::All => idx = 10,
+        MaschineButton::Pageleft => idx = 11,
+        MaschineButton::Pageright => idx = 12,
+
+        MaschineButton::Sampling => idx = 13,
+
+        MaschineButton::Noterepeat => idx = 14,
+        MaschineButton::Enter => idx = 15,
+        MaschineButton::Navright => idx = 16,
+        MaschineButton::Navleft => idx = 17,
+        MaschineButton::Tempo => idx = 18,
+        MaschineButton::Swing => idx = 19,
+        MaschineButton::Volume => idx = 20,
+
+        MaschineButton::Mute => idx = 21,
+        MaschineButton::Solo => idx = 22,
+        MaschineButton::Select => idx = 23,
+        MaschineButton::Duplicate => idx = 24,
+        MaschineButton::Navigate => idx = 25,
+        MaschineButton::Padmode => idx = 26,
+        MaschineButton::Pattern => idx = 27,
+        MaschineButton::Scene => idx = 28,
+        MaschineButton::Control => idx = 29,
+        MaschineButton::Step => idx = 30,
+        MaschineButton::Browse => idx = 31,
+
+        MaschineButton::GroupH => idx2 = 2,
+        MaschineButton::GroupG => idx2 = 9,
+        MaschineButton::GroupF => idx2 = 14,
+        MaschineButton::GroupE => idx2 = 22,
+        MaschineButton::GroupD => idx2 = 26,
+        MaschineButton::GroupC => idx2 = 34,
+        MaschineButton::GroupB => idx2 = 39,
+        MaschineButton::GroupA => idx2 = 48,
+        MaschineButton::Shift => idx2 = 47,
+        MaschineButton::Erase => idx2 = 56,
+        MaschineButton::Rec => idx2 = 54,
+        MaschineButton::Play => idx2 = 53,
+        MaschineButton::Grid => idx2 = 52,
+        MaschineButton::Stepright => idx2 = 51,
+        MaschineButton::Stepleft => idx2 = 50,
+        MaschineButton::Restart => idx2 = 49,
+
+        _ => return None,
+    };
+
+    Some((idx, idx2))
+}
+
+fn set_rgb_light(rgb: &mut [u8], color: u32, brightness: f32, gamma: f32) {
+    let level = |c: u32| {
+        let level = brightness * (c & 0xFF) as f32;
+        let level = if gamma == 1.0 {
+            level
+        } else {
+            255.0 * (level / 255.0).powf(gamma)
+        };
+
+        (0.5 * level) as u8
+    };
 
-    rgb[0] = (brightness * (((color >> 16) & 0xFF) as f32)) as u8;
-    rgb[1] = (brightness * (((color >> 8) & 0xFF) as f32)) as u8;
-    rgb[2] = (brightness * (((color) & 0xFF) as f32)) as u8;
+    rgb[0] = level(color >> 16);
+    rgb[1] = level(color >> 8);
+    rgb[2] = level(color);
 }
 
 impl Maschine for Mikro {
@@ -392,16 +777,58 @@ impl Maschine for Mikro {
     }
 
     fn write_lights(&mut self) {
-        unistd::write(self.dev, &self.light_buf).unwrap();
-        unistd::write(self.dev, &self.light_buf2).unwrap();
-        unistd::write(self.dev, &self.light_buf3).unwrap();
+        if self.faded_at.is_some() {
+            self.step_fades();
+        }
+
+        self.write_report(&self.light_buf);
+        self.write_report(&self.light_buf2);
+        self.write_report(&self.light_buf3);
+
+        // a fade that isn't over yet needs writing again
+        self.lights_dirty = self.faded_at.is_some();
+    }
+
+    fn lights_dirty(&self) -> bool {
+        self.lights_dirty
+    }
+
+    fn connected(&self) -> bool {
+        self.connected.get()
+    }
+
+    fn clear_lights(&mut self) {
+        // leave the report ids in byte 0 alone
+        for b in self.light_buf[1..].iter_mut() {
+            *b = 0;
+        }
+        for b in self.light_buf2[1..].iter_mut() {
+            *b = 0;
+        }
+        for b in self.light_buf3[1..].iter_mut() {
+            *b = 0;
+        }
+
+        self.lights = LightFrame::default();
+        self.shown_pads = [0.0; 16];
+        self.shown_buttons.clear();
+        self.faded_at = None;
+        self.lights_dirty = true;
     }
 
     fn set_pad_light(&mut self, pad: usize, color: u32, brightness: f32) {
-        let offset = 1 + (pad * 3);
-        let rgb = &mut self.light_buf[offset..(offset + 3)];
+        self.lights.pads[pad] = (color, brightness);
 
-        set_rgb_light(rgb, color, brightness);
+        // a new colour shows straight away, only the brightness fades
+        if self.fade == Duration::ZERO {
+            self.show_pad(pad, brightness);
+        } else {
+            let shown = self.shown_pads[pad];
+            self.show_pad(pad, shown);
+            if shown != brightness {
+                self.start_fade();
+            }
+        }
     }
 
     fn set_midi_note_base(&mut self, base: u8) {
@@ -412,99 +839,128 @@ impl Maschine for Mikro {
         return self.midi_note_base;
     }
 
-    fn set_button_light(&mut self, btn: MaschineButton, _color: u32, brightness: f32) {
-        let mut idx = 0;
-        let mut idx2 = 0;
-        match btn {
-            MaschineButton::F8 => idx = 1,
-            MaschineButton::F7 => idx = 2,
-            MaschineButton::F6 => idx = 3,
-            MaschineButton::F5 => idx = 4,
-            MaschineButton::F4 => idx = 5,
-            MaschineButton::F3 => idx = 6,
-            MaschineButton::F2 => idx = 7,
-            MaschineButton::F1 => idx = 8,
-
-            MaschineButton::Auto => idx = 9,
-            MaschineButton::All => idx = 10,
-            MaschineButton::Pageleft => idx = 11,
-            MaschineButton::Pageright => idx = 12,
-
-            MaschineButton::Sampling => idx = 13,
-
-            MaschineButton::Noterepeat => idx = 14,
-            MaschineButton::Enter => idx = 15,
-            MaschineButton::Navright => idx = 16,
-            MaschineButton::Navleft => idx = 17,
-            MaschineButton::Tempo => idx = 18,
-            MaschineButton::Swing => idx = 19,
-            MaschineButton::Volume => idx = 20,
-
-            MaschineButton::Mute => idx = 21,
-            MaschineButton::Solo => idx = 22,
-            MaschineButton::Select => idx = 23,
-            MaschineButton::Duplicate => idx = 24,
-            MaschineButton::Navigate => idx = 25,
-            MaschineButton::Padmode => idx = 26,
-            MaschineButton::Pattern => idx = 27,
-            MaschineButton::Scene => idx = 28,
-            MaschineButton::Control => idx = 29,
-            MaschineButton::Step => idx = 30,
-            MaschineButton::Browse => idx = 31,
-
-            MaschineButton::GroupH => idx2 = 2,
-            MaschineButton::GroupG => idx2 = 9,
-            MaschineButton::GroupF => idx2 = 14,
-            MaschineButton::GroupE => idx2 = 22,
-            MaschineButton::GroupD => idx2 = 26,
-            MaschineButton::GroupC => idx2 = 34,
-            MaschineButton::GroupB => idx2 = 39,
-            MaschineButton::GroupA => idx2 = 48,
-            MaschineButton::Shift => idx2 = 47,
-            MaschineButton::Erase => idx2 = 56,
-            MaschineButton::Rec => idx2 = 54,
-            MaschineButton::Play => idx2 = 53,
-            MaschineButton::Grid => idx2 = 52,
-            MaschineButton::Stepright => idx2 = 51,
-            MaschineButton::Stepleft => idx2 = 50,
-            MaschineButton::Restart => idx2 = 49,
-
-            _ => return,
-        };
-        if idx != 0 {
-            //println!("light this {}, brightness {}", idx, brightness);
-            self.light_buf2[idx] = brightness as u8;
-        } else {
-            self.light_buf3[idx2] = brightness as u8;
+    fn set_button_light(&mut self, btn: MaschineButton, color: u32, brightness: f32) {
+        if button_light_idx(btn).is_none() {
+            return;
+        }
+        self.lights.set_button(btn, color, brightness);
+
+        if self.fade == Duration::ZERO {
+            self.show_button(btn, brightness);
+        } else if self.shown_button(btn) != brightness {
+            self.start_fade();
         }
     }
 
+    fn set_led_fade(&mut self, fade: Duration) {
+        self.fade = fade;
+    }
+
+    // every pad is encoded again with it, to go out on the next write_lights
+    fn set_led_gamma(&mut self, gamma: f32) {
+        self.gamma = gamma;
+
+        for pad in 0..16 {
+            let shown = self.shown_pads[pad];
+            self.show_pad(pad, shown);
+        }
+    }
+
+    fn get_lights(&self) -> LightFrame {
+        self.lights.clone()
+    }
+
     fn readable(&mut self, handler: &mut dyn MaschineHandler) {
         let mut buf = [0u8; 256];
 
         let nbytes = match unistd::read(self.dev, &mut buf) {
+            Err(nix::Error::Sys(Errno::EIO)) | Err(nix::Error::Sys(Errno::ENODEV)) => {
+                self.connected.set(false);
+                return;
+            }
             Err(err) => panic!("read failed: {}", err.to_string()),
             Ok(nbytes) => nbytes,
         };
 
-        let report_nr = buf[0];
-        let buf = &buf[1..nbytes];
+        let events = match self.decoder.decode(&buf[..nbytes]) {
+            Some(events) => events,
+            None => {
+                println!(" :: {:2X}: got {} bytes", buf[0], nbytes);
+                return;
+            }
+        };
 
-        match report_nr {
-            0x01 => self.read_buttons(handler, &buf),
-            0x20 => self.read_pads(handler, &buf),
-            _ => println!(" :: {:2X}: got {} bytes", report_nr, nbytes),
+        for ev in events {
+            ev.dispatch(self, handler);
         }
     }
 
+    fn get_screen(&self) -> &[u8] {
+        &self.screen
+    }
+
+    fn get_screen_mut(&mut self) -> &mut [u8] {
+        &mut self.screen
+    }
+
+    fn send_screen(&mut self) {
+        if !self.screen_enabled {
+            return;
+        }
+
+        let mut shown = self.screen;
+        if self.screen_level < SCREEN_LEVELS {
+            for (idx, byte) in shown.iter_mut().enumerate() {
+                *byte &= screen_dither_mask(idx, self.screen_level);
+            }
+        }
+
+        let mut sent_now = shown;
+
+        match self.sent_screen {
+            Some(ref sent) => {
+                for (idx, (&byte, &was)) in shown.iter().zip(sent.iter()).enumerate() {
+                    if byte != was {
+                        self.send_screen_byte(idx, byte);
+                    }
+                }
+            }
+
+            None => {
+                // the first report has always gone out blank
+                self.send_screen_byte(0, 0);
+
+                for idx in 1..SCREEN_REPORTS {
+                    self.send_screen_byte(idx, shown.get(idx).cloned().unwrap_or(0));
+                }
+
+                // so that the next send puts right what the blank one left out
+                sent_now[0] = 0;
+            }
+        }
+
+        self.sent_screen = Some(sent_now);
+    }
+
     fn get_pad_pressure(&self, pad_idx: usize) -> Result<f32, ()> {
         match pad_idx {
-            0..=15 => Ok(self.pads[pad_idx].get_pressure()),
+            0..=15 => Ok(self.decoder.pads[pad_idx].get_pressure()),
             _ => Err(()),
         }
     }
 
+    fn force_full_redraw(&mut self) {
+        self.sent_screen = None;
+    }
+
     fn clear_screen(&mut self) {
+        self.screen = [0u8; SCREEN_BUF_SIZE];
+        self.sent_screen = Some(self.screen);
+
+        if !self.screen_enabled {
+            return;
+        }
+
         let mut screen_buf = [0u8; 1 + 8 + 512];
         let mut screen_buf2 = [0u8; 1 + 8 + 512];
 
@@ -535,14 +991,29 @@ impl Maschine for Mikro {
                 }
                 t += 1;
             }
-            unistd::write(self.dev, &screen_buf).unwrap();
-            unistd::write(self.dev, &screen_buf2).unwrap();
+            self.write_report(&screen_buf);
+            self.write_report(&screen_buf2);
         }
 
         println!("Screen clear done?");
     }
 
+    // goes out straight away, only the pixels it turns on or off
+    fn set_screen_brightness(&mut self, brightness: f32) {
+        let level = (brightness.clamp(0.0, 1.0) * SCREEN_LEVELS as f32).round() as u8;
+        if level == self.screen_level {
+            return;
+        }
+
+        self.screen_level = level;
+        self.send_screen();
+    }
+
     fn write_screen(&mut self) {
+        if !self.screen_enabled {
+            return;
+        }
+
         let mut limits = png::Limits::default();
         limits.bytes = 10 * 1024;
         let decoder = png::Decoder::new_with_limits(
@@ -553,20 +1024,8 @@ impl Maschine for Mikro {
         let mut picture = vec![0; reader.output_buffer_size()];
         let info = reader.next_frame(&mut picture).unwrap();
         let bytes = &picture[..info.buffer_size()];
-        let mut screen_buf = [0u8; 1 + 8 + 512];
         //println!("{}", bytes.len());
 
-        //let mut screen_buf2 = [0u8; 1 + 8+ 512];
-        screen_buf[0] = 0xE0;
-        screen_buf[5] = 0x08;
-        screen_buf[7] = 0x20;
-
-        screen_buf[1] = 0;
-        screen_buf[3] = 0;
-
-        let mut screen_writer = 9;
-        let mut steps = 0;
-        let mut bits = [0u8; 4097];
         let mut inc = 0;
         let mut ok = 0;
         let mut count2 = 0;
@@ -592,7 +1051,6 @@ impl Maschine for Mikro {
                 }
                 //println!("{}", swap);
             }
-            let mut binary = [0u8; 4097];
             if c < 65534 {
                 //print!("{}, ", bytes[count]);
                 let intval;
@@ -616,8 +1074,9 @@ impl Maschine for Mikro {
                     let combination = format!("{}{}{}{}{}{}{}{}", a1, a2, a3, a4, a5, a6, a7, a8);
                     intval = usize::from_str_radix(&combination, 2).unwrap();
                     ok = 0;
-                    binary[count2] = intval as u8;
-                    bits[count2] = binary[count2];
+                    if count2 < SCREEN_BUF_SIZE {
+                        self.screen[count2] = intval as u8;
+                    }
                     count2 += 1;
                     a1 = 0;
                     a2 = 0;
@@ -633,25 +1092,7 @@ impl Maschine for Mikro {
             //println!("{}", combination)
         }
 
-        for a in 0..bits.len() {
-            if screen_writer == 10 {
-                if steps <= 30 {
-                    screen_buf[1] += 1;
-                    steps += 1;
-                    screen_writer = 9;
-                    screen_buf[screen_writer] = bits[a];
-                } else {
-                    screen_buf[3] += 1;
-                    screen_buf[1] = 0;
-                    steps = 0;
-                    screen_writer = 9;
-                    screen_buf[screen_writer] = bits[a];
-                }
-            }
-            //println!("{}", bits[a]);
-            unistd::write(self.dev, &screen_buf).unwrap();
-            screen_writer += 1;
-        }
+        self.send_screen();
         println!("writescreen");
     }
 }
@@ -15,6 +15,6 @@
 //  License along with this program.  If not, see
 //  <http://www.gnu.org/licenses/>.
 
-mod mikro;
+pub mod mikro;
 
-pub use self::mikro::Mikro;
+pub use self::mikro::{nearest_palette_color, LedColors, Mikro, LED_PALETTE};
@@ -0,0 +1,130 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+use std::os::unix::io::RawFd;
+
+use base::{LightFrame, Maschine, MaschineButton, MaschineHandler};
+use devices::mk2::mikro::SCREEN_BUF_SIZE;
+
+// a device that isn't there, which just remembers what it was told so it can be checked
+pub struct MockMaschine {
+    pub pad_pressures: [f32; 16],
+    pub pad_lights: [(u32, f32); 16],
+    pub button_lights: Vec<(MaschineButton, u32, f32)>,
+    pub screen_brightness: f32,
+
+    midi_note_base: u8,
+    screen: Vec<u8>,
+}
+
+impl Default for MockMaschine {
+    fn default() -> Self {
+        MockMaschine {
+            pad_pressures: [0.0; 16],
+            pad_lights: [(0, 0.0); 16],
+            button_lights: vec![],
+            screen_brightness: 1.0,
+
+            midi_note_base: 0,
+            screen: vec![0; SCREEN_BUF_SIZE],
+        }
+    }
+}
+
+impl MockMaschine {
+    pub fn button_light(&self, btn: MaschineButton) -> Option<(u32, f32)> {
+        self.button_lights
+            .iter()
+            .find(|&&(b, _, _)| b == btn)
+            .map(|&(_, color, brightness)| (color, brightness))
+    }
+}
+
+impl Maschine for MockMaschine {
+    // nothing to poll, it never has any input
+    fn get_fd(&self) -> RawFd {
+        -1
+    }
+
+    fn get_pad_pressure(&self, pad_idx: usize) -> Result<f32, ()> {
+        self.pad_pressures.get(pad_idx).cloned().ok_or(())
+    }
+
+    fn get_midi_note_base(&self) -> u8 {
+        self.midi_note_base
+    }
+
+    fn set_midi_note_base(&mut self, base: u8) {
+        self.midi_note_base = base;
+    }
+
+    fn set_pad_light(&mut self, pad_idx: usize, color: u32, brightness: f32) {
+        self.pad_lights[pad_idx] = (color, brightness);
+    }
+
+    fn set_button_light(&mut self, btn: MaschineButton, color: u32, brightness: f32) {
+        self.button_lights.retain(|&(b, _, _)| b != btn);
+        self.button_lights.push((btn, color, brightness));
+    }
+
+    fn get_lights(&self) -> LightFrame {
+        LightFrame {
+            pads: self.pad_lights,
+            buttons: self.button_lights.clone(),
+        }
+    }
+
+    fn readable(&mut self, _: &mut dyn MaschineHandler) {}
+
+    fn get_screen(&self) -> &[u8] {
+        &self.screen
+    }
+
+    fn get_screen_mut(&mut self) -> &mut [u8] {
+        &mut self.screen
+    }
+
+    fn send_screen(&mut self) {}
+    fn force_full_redraw(&mut self) {}
+
+    fn clear_screen(&mut self) {
+        for byte in self.screen.iter_mut() {
+            *byte = 0;
+        }
+    }
+
+    fn clear_lights(&mut self) {
+        self.pad_lights = [(0, 0.0); 16];
+        self.button_lights.clear();
+    }
+
+    fn write_lights(&mut self) {}
+
+    fn lights_dirty(&self) -> bool {
+        false
+    }
+
+    fn connected(&self) -> bool {
+        true
+    }
+
+    fn set_screen_brightness(&mut self, brightness: f32) {
+        self.screen_brightness = brightness;
+    }
+
+    fn write_screen(&mut self) {}
+}
@@ -15,4 +15,6 @@
 //  License along with this program.  If not, see
 //  <http://www.gnu.org/licenses/>.
 
+pub mod keyboard;
+pub mod mock;
 pub mod mk2;
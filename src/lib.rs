@@ -0,0 +1,294 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+// the device drivers and the event loop, for programs that want to drive the hardware with a
+// MaschineHandler of their own, and the MIDI ports, OSC socket and message names to speak
+// with. src/main.rs is one, speaking OSC and MIDI
+
+use std::os::unix::io::RawFd;
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+extern crate nix;
+use nix::errno::Errno;
+use nix::poll::*;
+
+extern crate alsa_seq;
+extern crate hsl;
+extern crate midi;
+extern crate tinyosc;
+
+pub mod base;
+pub mod devices;
+pub mod transport;
+
+use base::{Clock, Maschine, MaschineHandler, SystemClock};
+
+// after this many panics in handler code we stop trying to recover and exit
+const MAX_HANDLER_PANICS: usize = 8;
+
+fn recover_from_panic(
+    dev: &mut dyn Maschine,
+    handler: &mut dyn MaschineHandler,
+    what: &str,
+    panics: &mut usize,
+) {
+    *panics += 1;
+    println!(
+        " :: panic while handling {} ({}/{}), resetting lights and screen",
+        what, *panics, MAX_HANDLER_PANICS
+    );
+
+    // the device itself may be what's broken, so a failing reset must not take us down
+    let reset = panic::catch_unwind(AssertUnwindSafe(|| {
+        dev.clear_lights();
+        dev.write_lights();
+        dev.clear_screen();
+
+        // whatever was on screen before the panic is no longer worth diffing against
+        dev.force_full_redraw();
+    }));
+
+    if reset.is_err() || *panics >= MAX_HANDLER_PANICS {
+        shut_down(handler, "giving up");
+    }
+}
+
+// ms since the epoch, as the watchdog can only share plain integers with the event loop
+fn now_ms() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_millis() as u64,
+        Err(_) => 0,
+    }
+}
+
+// shared between the event loop and the watchdog thread
+pub struct Watchdog {
+    // the time of the event loop's last pass
+    heartbeat: AtomicU64,
+
+    // set when the watchdog wants out, for the event loop to shut down on its next pass
+    gave_up: AtomicBool,
+}
+
+// the event loop stores the time of its last pass in the watchdog, which complains when it
+// hasn't moved for `threshold`. with `exit` it also has the loop shut down, so that whatever
+// supervises us can restart and reinitialise the device, and exits itself if the loop is too
+// stuck to do even that
+pub fn spawn_watchdog(threshold: Duration, exit: bool) -> Arc<Watchdog> {
+    let watchdog = Arc::new(Watchdog {
+        heartbeat: AtomicU64::new(now_ms()),
+        gave_up: AtomicBool::new(false),
+    });
+    let shared = watchdog.clone();
+    let threshold_ms = threshold.as_millis() as u64;
+
+    thread::spawn(move || {
+        let mut stalled = false;
+
+        loop {
+            thread::sleep(threshold / 4);
+
+            let since = now_ms().saturating_sub(shared.heartbeat.load(Ordering::Relaxed));
+
+            if since < threshold_ms {
+                if stalled {
+                    println!(" :: event loop running again");
+                    stalled = false;
+                }
+
+                continue;
+            }
+
+            if !stalled {
+                println!(" :: event loop stalled for {}ms", since);
+                stalled = true;
+            }
+
+            if !exit {
+                continue;
+            }
+
+            // the loop ending the notes that are still on is worth waiting another threshold
+            // for, in case it comes back
+            if shared.gave_up.swap(true, Ordering::Relaxed) {
+                println!(" :: watchdog giving up, exiting");
+                process::exit(1);
+            }
+            thread::sleep(threshold);
+        }
+    });
+
+    watchdog
+}
+
+// how run() paces itself
+pub struct RunConfig {
+    // how often the handler's tick() is called
+    pub tick_interval: Duration,
+
+    // the shortest time between light updates, and how often they're resent unchanged
+    pub light_interval: Duration,
+    pub light_refresh: Duration,
+
+    // told the time of every pass through the loop, from spawn_watchdog()
+    pub watchdog: Option<Arc<Watchdog>>,
+
+    // what ticks and light updates are timed by. the watchdog always goes by the real time
+    pub clock: Rc<dyn Clock>,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        RunConfig {
+            tick_interval: Duration::from_millis(16),
+
+            light_interval: Duration::from_millis(16),
+            light_refresh: Duration::from_secs(1),
+
+            watchdog: None,
+            clock: Rc::new(SystemClock),
+        }
+    }
+}
+
+// what poll() reports for an fd that's gone bad, even without it being asked for
+fn poll_failed(fd: &PollFd) -> bool {
+    fd.revents()
+        .unwrap()
+        .intersects(POLLERR | POLLHUP | POLLNVAL)
+}
+
+fn shut_down(handler: &mut dyn MaschineHandler, why: &str) -> ! {
+    println!(" :: {}, exiting", why);
+
+    // a handler that's been panicking may well do it again, which mustn't keep us running
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| handler.disconnected()));
+    process::exit(1);
+}
+
+// hands device input, input on the handler's own fds and timer ticks to the handler until the
+// process exits. a panic in any of them resets the device and carries on, up to a point
+pub fn run(dev: &mut dyn Maschine, handler: &mut dyn MaschineHandler, config: &RunConfig) -> ! {
+    let mut fds = vec![PollFd::new(dev.get_fd(), POLLIN, EventFlags::empty())];
+
+    let handler_fds: Vec<RawFd> = handler.get_fds();
+    for &fd in &handler_fds {
+        fds.push(PollFd::new(fd, POLLIN, EventFlags::empty()));
+    }
+
+    let timeout = config.tick_interval.as_millis() as i32;
+
+    let clock = &*config.clock;
+
+    let mut now = clock.now();
+    let mut lights_written = now;
+    let mut panics = 0;
+
+    loop {
+        if let Some(ref watchdog) = config.watchdog {
+            if watchdog.gave_up.load(Ordering::Relaxed) {
+                shut_down(handler, "watchdog giving up");
+            }
+
+            watchdog.heartbeat.store(now_ms(), Ordering::Relaxed);
+        }
+
+        // a signal arriving mid-poll isn't a reason to go down, the next pass polls again
+        match poll(&mut fds, timeout) {
+            Ok(_) => {}
+            Err(nix::Error::Sys(Errno::EINTR)) => continue,
+            Err(err) => shut_down(handler, &format!("poll failed: {}", err)),
+        }
+
+        // a dead fd polls readable forever without ever giving anything back
+        if poll_failed(&fds[0]) {
+            shut_down(handler, "device disconnected");
+        }
+
+        if fds[0].revents().unwrap().contains(POLLIN) {
+            handler.report_arrived(clock.now());
+
+            let res = panic::catch_unwind(AssertUnwindSafe(|| dev.readable(handler)));
+
+            if res.is_err() {
+                recover_from_panic(dev, handler, "device input", &mut panics);
+            }
+
+            if !dev.connected() {
+                shut_down(handler, "device disconnected");
+            }
+        }
+
+        for (i, &fd) in handler_fds.iter().enumerate() {
+            if poll_failed(&fds[i + 1]) {
+                shut_down(handler, &format!("input fd {} failed", fd));
+            }
+
+            if !fds[i + 1].revents().unwrap().contains(POLLIN) {
+                continue;
+            }
+
+            let res = panic::catch_unwind(AssertUnwindSafe(|| handler.read_input(dev, fd)));
+
+            if res.is_err() {
+                recover_from_panic(dev, handler, "handler input", &mut panics);
+            }
+        }
+
+        if clock.since(now) >= config.tick_interval {
+            let res = panic::catch_unwind(AssertUnwindSafe(|| handler.tick(dev)));
+
+            if res.is_err() {
+                recover_from_panic(dev, handler, "timer tick", &mut panics);
+            }
+
+            now = clock.now();
+        }
+
+        let res = panic::catch_unwind(AssertUnwindSafe(|| handler.flush()));
+
+        if res.is_err() {
+            recover_from_panic(dev, handler, "flush", &mut panics);
+        }
+
+        // changed lights go out as soon as the rate limit allows, the rest only get refreshed
+        // every so often in case the device missed a report
+        let since_lights = clock.since(lights_written);
+        if (dev.lights_dirty() && since_lights >= config.light_interval)
+            || since_lights >= config.light_refresh
+        {
+            let res = panic::catch_unwind(AssertUnwindSafe(|| dev.write_lights()));
+
+            if res.is_err() {
+                recover_from_panic(dev, handler, "light update", &mut panics);
+            }
+
+            lights_written = clock.now();
+        }
+
+        // anything written this pass may have been what found the device gone
+        if !dev.connected() {
+            shut_down(handler, "device disconnected");
+        }
+    }
+}
@@ -15,24 +15,29 @@
 //  License along with this program.  If not, see
 //  <http://www.gnu.org/licenses/>.
 
+use std::cell::Cell;
+use std::collections::VecDeque;
 use std::env;
-use std::os::unix::io::AsRawFd;
+use std::fs;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::path::Path;
+use std::process;
+use std::rc::Rc;
 
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::net::{SocketAddr, UdpSocket};
 
 use std::time::{Duration, SystemTime};
 
 extern crate nix;
 use nix::fcntl::{O_NONBLOCK, O_RDWR};
-use nix::poll::*;
 use nix::{fcntl, sys};
 
 extern crate alsa_seq;
 extern crate midi;
-use alsa_seq::*;
 use midi::*;
 
+extern crate hex;
+
 extern crate hsl;
 use hsl::HSL;
 
@@ -40,1108 +45,3348 @@ use hsl::HSL;
 extern crate tinyosc;
 use tinyosc as osc;
 
-mod base;
-mod devices;
+extern crate maschine;
+use maschine::transport::messages::{
+    btn_to_osc_button_map, button_rpn, osc_button_name, osc_button_to_btn_map, osc_pad_path,
+    parse_color, parse_hsl, parse_light_frame, parse_sysex,
+};
+use maschine::transport::{udp, AlsaSequencer, MidiPorts};
+use maschine::{base, devices, RunConfig};
 
-use base::{Maschine, MaschineButton, MaschineHandler};
+mod config;
+mod selftest;
 
-fn ev_loop(dev: &mut dyn Maschine, mhandler: &mut MHandler) {
-    let mut fds = [
-        PollFd::new(dev.get_fd(), POLLIN, EventFlags::empty()),
-        PollFd::new(mhandler.osc_socket.as_raw_fd(), POLLIN, EventFlags::empty()),
-    ];
+#[cfg(test)]
+mod test;
 
-    let mut now = SystemTime::now();
-    let timer_interval = Duration::from_millis(16);
+use base::debounce::Debounce;
+use base::humanize::Humanize;
+use base::release_lockout::ReleaseLockout;
+use base::screen::{self, SCREEN_HEIGHT, SCREEN_WIDTH};
+use base::{Clock, Maschine, MaschineButton, MaschineEvent, MaschineHandler, SystemClock};
+use config::{Config, Problem, CHANNELS};
 
-    loop {
-        poll(&mut fds, 16).unwrap();
+fn usage(prog_name: &String) {
+    println!("usage: {} [options] <hidraw device>", prog_name);
+    println!("       {} [options] --input keyboard", prog_name);
+    println!("       {} [options] --self-test", prog_name);
+    println!("{}", config::OPTIONS_HELP);
+}
 
-        if fds[0].revents().unwrap().contains(POLLIN) {
-            dev.readable(mhandler);
-        }
+const OSC_LISTEN_PORT: u16 = 42434;
+const OSC_SEND_PORT: u16 = 42435;
 
-        if fds[1].revents().unwrap().contains(POLLIN) {
-            mhandler.recv_osc_msg(dev);
-        }
+const MIDI_CLIENT_NAME: &str = "maschine.rs";
+const MIDI_OUT_PORT_NAME: &str = "Pads MIDI";
+const MIDI_IN_PORT_NAME: &str = "input";
 
-        if now.elapsed().unwrap() >= timer_interval {
-            dev.write_lights();
-            now = SystemTime::now();
-        }
-    }
+// device drivers built into this binary
+const DEVICES: &[&str] = &["mk2 (Mikro)", "keyboard"];
+
+// default brightness of pads that aren't being pressed
+const PAD_RELEASED_BRIGHTNESS: f32 = 0.015;
+
+// button LEDs take a level up to this, where the pads take a brightness up to 1
+const BUTTON_LIGHT_MAX: f32 = 127.0;
+
+// the level for a button the driver lights itself, from a pad-style brightness
+fn button_level(brightness: f32) -> f32 {
+    brightness * BUTTON_LIGHT_MAX
 }
 
-fn usage(prog_name: &String) {
-    println!("usage: {} <hidraw device>", prog_name);
+// framebuffer bytes per /maschine/screen/dump reply, hex encoded this stays well under
+// the size of a typical UDP datagram
+const SCREEN_DUMP_CHUNK: usize = 256;
+
+// tempo taps averaged together, and how long after the last one a new tap starts over
+const TEMPO_TAPS: usize = 5;
+const TEMPO_TAP_TIMEOUT: Duration = Duration::from_secs(2);
+
+const DEFAULT_TEMPO: f32 = 120.0;
+
+// the metronome counts bars of this many beats, clicking the accent on the first
+const METRONOME_BEATS_PER_BAR: u64 = 4;
+const METRONOME_VELOCITY: U7 = 100;
+const METRONOME_ACCENT_VELOCITY: U7 = 127;
+const METRONOME_CLICK_LENGTH: Duration = Duration::from_millis(50);
+
+// how often --latency reports what it measured
+const LATENCY_WINDOW: Duration = Duration::from_secs(5);
+
+// how much one encoder step changes the screen brightness by, in --encoder-mode screen
+const SCREEN_BRIGHTNESS_STEP: f32 = 1.0 / 16.0;
+
+// where --encoder-mode wrap and clamp go back to when the encoder's pushed
+const ENCODER_CENTRE: i32 = 64;
+
+// how long the encoder readout stays up after the last turn
+const ENCODER_READOUT_TIME: Duration = Duration::from_millis(1500);
+
+// the highest note base at which every pad still has a MIDI note
+const HIGHEST_NOTE_BASE: u8 = 127 - 15;
+
+// how long after a button changes before another change of it is believed
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(5);
+
+// --light-test lights one pad or button per step, the pads in a rainbow and then these
+const LIGHT_TEST_STEP: Duration = Duration::from_millis(40);
+const LIGHT_TEST_BUTTONS: [MaschineButton; 16] = [
+    MaschineButton::GroupA,
+    MaschineButton::GroupB,
+    MaschineButton::GroupC,
+    MaschineButton::GroupD,
+    MaschineButton::GroupE,
+    MaschineButton::GroupF,
+    MaschineButton::GroupG,
+    MaschineButton::GroupH,
+    MaschineButton::Restart,
+    MaschineButton::Stepleft,
+    MaschineButton::Stepright,
+    MaschineButton::Grid,
+    MaschineButton::Play,
+    MaschineButton::Rec,
+    MaschineButton::Erase,
+    MaschineButton::Shift,
+];
+
+// the note base devices start out at
+const DEFAULT_NOTE_BASE: u8 = 48;
+
+// what to do with pads whose note would land above MIDI note 127
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum NoteRangePolicy {
+    Clamp,
+    Drop,
 }
 
-const PAD_RELEASED_BRIGHTNESS: f32 = 0.015;
+// what happens to pad hits past the --roll-limit for a tick
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum RollOverflow {
+    // played on the following ticks, in the order they came in
+    Queue,
 
-#[allow(dead_code)]
-enum PressureShape {
-    Linear,
-    Exponential(f32),
-    Constant(f32),
+    // not played at all, so letting go of the pad sends nothing either
+    Drop,
 }
 
-struct MHandler<'a> {
-    color: HSL,
+// what letting go of one of several pads holding the same note does
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum SharedNotePolicy {
+    // the note keeps going until the last of them is let go
+    Last,
 
-    seq_handle: &'a SequencerHandle,
-    seq_port: &'a SequencerPort<'a>,
-    seq_handle_in: &'a SequencerHandle,
-    seq_port_in: &'a SequencerPort<'a>,
+    // every release sends a note off, cutting the note short for the others
+    Each,
+}
 
-    pressure_shape: PressureShape,
-    send_aftertouch: bool,
+// how a held pad's light follows its pressure
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum PadLight {
+    Follow,
 
-    osc_socket: &'a UdpSocket,
-    osc_outgoing_addr: SocketAddr,
+    // stays at the hardest the pad has been pressed since it was hit, so that aftertouch
+    // wobble doesn't flicker it
+    Peak,
 }
 
-fn osc_button_to_btn_map(osc_button: &str) -> Option<MaschineButton> {
-    match osc_button {
-        "restart" => Some(MaschineButton::Restart),
-        "step_left" => Some(MaschineButton::Stepleft),
-        "step_right" => Some(MaschineButton::Stepright),
-        "grid" => Some(MaschineButton::Grid),
-        "play" => Some(MaschineButton::Play),
-        "rec" => Some(MaschineButton::Rec),
-        "stop" => Some(MaschineButton::Erase),
-        "shift" => Some(MaschineButton::Shift),
-
-        "browse" => Some(MaschineButton::Browse),
-        "sampling" => Some(MaschineButton::Sampling),
-        "note_repeat" => Some(MaschineButton::Noterepeat),
-
-        "encoder" => Some(MaschineButton::Encoder),
-
-        "f1" => Some(MaschineButton::F1),
-        "f2" => Some(MaschineButton::F2),
-        "f3" => Some(MaschineButton::F3),
-        "f4" => Some(MaschineButton::F4),
-        "f5" => Some(MaschineButton::F5),
-        "f6" => Some(MaschineButton::F6),
-        "f7" => Some(MaschineButton::F7),
-        "f8" => Some(MaschineButton::F8),
-
-        "swing" => Some(MaschineButton::Swing),
-        "step" => Some(MaschineButton::Step),
-        "volume" => Some(MaschineButton::Volume),
-
-        "enter" => Some(MaschineButton::Enter),
-        "auto" => Some(MaschineButton::Auto),
-        "all" => Some(MaschineButton::All),
-        "navigate" => Some(MaschineButton::Navigate),
-        "tempo" => Some(MaschineButton::Tempo),
-        //"stop" => Some(MaschineButton::Erase),
-
-        "control" => Some(MaschineButton::Control),
-        "nav" => Some(MaschineButton::Nav),
-        "nav_left" => Some(MaschineButton::Navleft),
-        "nav_right" => Some(MaschineButton::Navright),
-        "main" => Some(MaschineButton::Main),
-
-        "scene" => Some(MaschineButton::Scene),
-        "pattern" => Some(MaschineButton::Pattern),
-        "pad_mode" => Some(MaschineButton::Padmode),
-        "view" => Some(MaschineButton::View),
-        "duplicate" => Some(MaschineButton::Duplicate),
-        "select" => Some(MaschineButton::Select),
-        "solo" => Some(MaschineButton::Solo),
-        "mute" => Some(MaschineButton::Mute),
-
-        "group_a" => Some(MaschineButton::GroupA),
-        "group_b" => Some(MaschineButton::GroupB),
-        "group_c" => Some(MaschineButton::GroupC),
-        "group_d" => Some(MaschineButton::GroupD),
-        "group_e" => Some(MaschineButton::GroupE),
-        "group_f" => Some(MaschineButton::GroupF),
-        "group_g" => Some(MaschineButton::GroupG),
-        "group_h" => Some(MaschineButton::GroupH),
-
-        "page_right" => Some(MaschineButton::Pageright),
-        "page_left" => Some(MaschineButton::Pageleft),
-
-        _ => None,
-    }
+// what /maschine/encoder reports. relative is just the steps turned, the others add a 0-127
+// position kept by the driver, which either wraps around or stops at the ends
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum EncoderMode {
+    Relative,
+    Wrap,
+    Clamp,
+
+    // turns set the screen's brightness instead, and aren't reported
+    Screen,
+
+    // each step moves the note base a semitone, reported as /maschine/midi_note_base
+    NoteBase,
+
+    // steps go through --encoder-values, reported as /maschine/encoder/value
+    ListWrap,
+    ListClamp,
 }
 
-fn btn_to_osc_button_map(btn: MaschineButton) -> &'static str {
-    match btn {
-        MaschineButton::Restart => "restart",
-        MaschineButton::Stepleft => "step_left",
-        MaschineButton::Stepright => "step_right",
-        MaschineButton::Grid => "grid",
-        MaschineButton::Play => "play",
-        MaschineButton::Rec => "rec",
-        MaschineButton::Erase => "stop",
-        MaschineButton::Shift => "shift",
-
-        MaschineButton::Browse => "browse",
-        MaschineButton::Sampling => "sampling",
-        MaschineButton::Noterepeat => "note_repeat",
-
-        MaschineButton::Encoder => "encoder",
-
-        MaschineButton::F1 => "f1",
-        MaschineButton::F2 => "f2",
-        MaschineButton::F3 => "f3",
-        MaschineButton::F4 => "f4",
-        MaschineButton::F5 => "f5",
-        MaschineButton::F6 => "f6",
-        MaschineButton::F7 => "f7",
-        MaschineButton::F8 => "f8",
-
-        MaschineButton::Swing => "swing",
-        MaschineButton::Step => "step",
-        MaschineButton::Volume => "volume",
-
-        MaschineButton::Enter => "enter",
-        MaschineButton::Auto => "auto",
-        MaschineButton::All => "all",
-        MaschineButton::Navigate => "navigate",
-        MaschineButton::Tempo => "tempo",
-
-        MaschineButton::Control => "control",
-        MaschineButton::Nav => "nav",
-        MaschineButton::Navleft => "nav_left",
-        MaschineButton::Navright => "nav_right",
-        MaschineButton::Main => "main",
-
-        MaschineButton::Scene => "scene",
-        MaschineButton::Pattern => "pattern",
-        MaschineButton::Padmode => "pad_mode",
-        MaschineButton::View => "view",
-        MaschineButton::Duplicate => "duplicate",
-        MaschineButton::Select => "select",
-        MaschineButton::Solo => "solo",
-        MaschineButton::Mute => "mute",
-
-        MaschineButton::GroupA => "group_a",
-        MaschineButton::GroupB => "group_b",
-        MaschineButton::GroupC => "group_c",
-        MaschineButton::GroupD => "group_d",
-        MaschineButton::GroupE => "group_e",
-        MaschineButton::GroupF => "group_f",
-        MaschineButton::GroupG => "group_g",
-        MaschineButton::GroupH => "group_h",
-
-        MaschineButton::Pageright => "page_right",
-        MaschineButton::Pageleft => "page_left",
-        MaschineButton::R1 => "R1",
-        MaschineButton::R2 => "R2",
-        MaschineButton::R3 => "R3",
-        MaschineButton::R4 => "R4",
-        MaschineButton::R5 => "R5",
-        MaschineButton::R6 => "R6",
-        MaschineButton::R7 => "R7",
-        MaschineButton::R8 => "R8",
-
-        MaschineButton::A1 => "A1",
-        MaschineButton::A2 => "A2",
-        MaschineButton::A3 => "A3",
-        MaschineButton::A4 => "A4",
-        MaschineButton::A5 => "A5",
-        MaschineButton::A6 => "A6",
-        MaschineButton::A7 => "A7",
-        MaschineButton::A8 => "A8",
-
-        MaschineButton::B1 => "B1",
-        MaschineButton::B2 => "B2",
-        MaschineButton::B3 => "B3",
-        MaschineButton::B4 => "B4",
-        MaschineButton::B5 => "B5",
-        MaschineButton::B6 => "B6",
-        MaschineButton::B7 => "B7",
-        MaschineButton::B8 => "B8",
-
-        MaschineButton::C1 => "C1",
-        MaschineButton::C2 => "C2",
-        MaschineButton::C3 => "C3",
-        MaschineButton::C4 => "C4",
-        MaschineButton::C5 => "C5",
-        MaschineButton::C6 => "C6",
-        MaschineButton::C7 => "C7",
-        MaschineButton::C8 => "C8",
-
-        MaschineButton::D1 => "D1",
-        MaschineButton::D2 => "D2",
-        MaschineButton::D3 => "D3",
-        MaschineButton::D4 => "D4",
-        MaschineButton::D5 => "D5",
-        MaschineButton::D6 => "D6",
-        MaschineButton::D7 => "D7",
-        MaschineButton::D8 => "D8",
-
-        MaschineButton::E1 => "E1",
-        MaschineButton::E2 => "E2",
-        MaschineButton::E3 => "E3",
-        MaschineButton::E4 => "E4",
-        MaschineButton::E5 => "E5",
-        MaschineButton::E6 => "E6",
-        MaschineButton::E7 => "E7",
-        MaschineButton::E8 => "E8",
-
-        MaschineButton::FF1 => "FF1",
-        MaschineButton::FF2 => "FF2",
-        MaschineButton::FF3 => "FF3",
-        MaschineButton::FF4 => "FF4",
-        MaschineButton::FF5 => "FF5",
-        MaschineButton::FF6 => "FF6",
-        MaschineButton::FF7 => "FF8",
-        MaschineButton::FF8 => "FF8",
-
-        MaschineButton::G1 => "G1",
-        MaschineButton::G2 => "G2",
-        MaschineButton::G3 => "G3",
-        MaschineButton::G4 => "G4",
-        MaschineButton::G5 => "G5",
-        MaschineButton::G6 => "G6",
-        MaschineButton::G7 => "G7",
-        MaschineButton::G8 => "G8",
-
-        MaschineButton::H1 => "H1",
-        MaschineButton::H2 => "H2",
-        MaschineButton::H3 => "H3",
-        MaschineButton::H4 => "H4",
-        MaschineButton::H5 => "H5",
-        MaschineButton::H6 => "H6",
-        MaschineButton::H7 => "H7",
-        MaschineButton::H8 => "H8",
-
-        MaschineButton::I1 => "I1",
-        MaschineButton::I2 => "I2",
-        MaschineButton::I3 => "I3",
-        MaschineButton::I4 => "I4",
-        MaschineButton::I5 => "I5",
-        MaschineButton::I6 => "I6",
-        MaschineButton::I7 => "I7",
-        MaschineButton::I8 => "I8",
-
-        MaschineButton::J1 => "J1",
-        MaschineButton::J2 => "J2",
-        MaschineButton::J3 => "J3",
-        MaschineButton::J4 => "J4",
-        MaschineButton::J5 => "J5",
-        MaschineButton::J6 => "J6",
-        MaschineButton::J7 => "J7",
-        MaschineButton::J8 => "J8",
-
-        MaschineButton::K1 => "K1",
-        MaschineButton::K2 => "K2",
-        MaschineButton::K3 => "K3",
-        MaschineButton::K4 => "K4",
-        MaschineButton::K5 => "K5",
-        MaschineButton::K6 => "K6",
-        MaschineButton::K7 => "K7",
-        MaschineButton::K8 => "K8",
-
-        MaschineButton::L1 => "L1",
-        MaschineButton::L2 => "L2",
-        MaschineButton::L3 => "L3",
-        MaschineButton::L4 => "L4",
-        MaschineButton::L5 => "L5",
-        MaschineButton::L6 => "L6",
-        MaschineButton::L7 => "L7",
-        MaschineButton::L8 => "L8",
-
-        MaschineButton::M1 => "M1",
-        MaschineButton::M2 => "M2",
-        MaschineButton::M3 => "M3",
-        MaschineButton::M4 => "M4",
-        MaschineButton::M5 => "M5",
-        MaschineButton::M6 => "M6",
-        MaschineButton::M7 => "M7",
-        MaschineButton::M8 => "M8",
-
-        MaschineButton::N1 => "N1",
-        MaschineButton::N2 => "N2",
-        MaschineButton::N3 => "N3",
-        MaschineButton::N4 => "N4",
-        MaschineButton::N5 => "N5",
-        MaschineButton::N6 => "N6",
-        MaschineButton::N7 => "N7",
-        MaschineButton::N8 => "N8",
-
-        MaschineButton::O1 => "O1",
-        MaschineButton::O2 => "O2",
-        MaschineButton::O3 => "O3",
-        MaschineButton::O4 => "O4",
-        MaschineButton::O5 => "O5",
-        MaschineButton::O6 => "O6",
-        MaschineButton::O7 => "O7",
-        MaschineButton::O8 => "O8",
-
-        MaschineButton::P1 => "P1",
-        MaschineButton::P2 => "P2",
-        MaschineButton::P3 => "P3",
-        MaschineButton::P4 => "P4",
-        MaschineButton::P5 => "P5",
-        MaschineButton::P6 => "P6",
-        MaschineButton::P7 => "P7",
-        MaschineButton::P8 => "P8",
-    }
+// what MIDI the list modes send the selected value as
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum EncoderMidi {
+    Program,
+    Cc(U7),
 }
 
-impl<'a> MHandler<'a> {
-    fn pad_color(&self) -> u32 {
-        let (r, g, b) = self.color.to_rgb();
+// how the steps are written in /maschine/encoder, for receivers that can't take an int
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum EncoderFormat {
+    Int,
+    Float,
 
-        ((r as u32) << 16) | ((g as u32) << 8) | (b as u32)
-    }
+    // the 0-127 position alone, in place of the steps
+    Absolute,
 
-    fn pressure_to_vel(&self, pressure: f32) -> U7 {
-        (match self.pressure_shape {
-            PressureShape::Linear => pressure,
-            PressureShape::Exponential(power) => pressure.powf(power),
-            PressureShape::Constant(c_pressure) => c_pressure,
-        } * 127.0) as U7
-    }
+    // "+1" or "-1", however many steps it was
+    Direction,
+}
 
-    #[allow(dead_code)]
-    fn update_pad_colors(&self, maschine: &mut dyn Maschine) {
-        for i in 0..16 {
-            let brightness = match maschine.get_pad_pressure(i).unwrap() {
-                b if b == 0.0 => PAD_RELEASED_BRIGHTNESS,
-                pressure @ _ => pressure.sqrt(),
-            };
+// what pad aftertouch is sent as, when it's sent at all
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum AftertouchMode {
+    Poly,
 
-            maschine.set_pad_light(i, self.pad_color(), brightness);
-        }
-    }
+    // --aftertouch-cc, for synths that take expression better than poly pressure
+    Cc,
 
-    fn recv_osc_msg(&self, maschine: &mut dyn Maschine) {
-        let mut buf = [0u8; 128];
+    // one pressure per channel, for synths that take nothing else
+    Channel,
+}
 
-        let nbytes = match self.osc_socket.recv_from(&mut buf) {
-            Ok((nbytes, _)) => nbytes,
-            Err(e) => {
-                println!(" :: error in recv_from(): {}", e);
-                return;
-            }
-        };
+// with CC or channel aftertouch, how the pressures of the pads held on a channel become the one
+// value that goes out
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum AftertouchHeld {
+    // every pad sends its own, so the controller follows whichever moved last. channel pressure
+    // has no use for this and takes the max instead
+    Each,
+    Max,
+
+    // the pad hit most recently
+    Latest,
+    Average,
+
+    // clamped to full pressure
+    Sum,
+}
 
-        let msg = match osc::Message::deserialize(&buf[..nbytes]) {
-            Ok(msg) => msg,
-            Err(_) => {
-                println!(" :: couldn't decode OSC message :c");
-                return;
-            }
-        };
+// what a button sends as MIDI, when it's been bound to something other than its RPN
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ButtonAction {
+    // with a fixed velocity, the button's status byte from the report isn't one
+    Note {
+        note: U7,
+        channel: Channel,
+        velocity: U7,
+    },
+}
 
-        self.handle_osc_messge(maschine, &msg);
-    }
+// a button held for `delay` fires again every `interval` until it's let go
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct AutoRepeat {
+    delay: Duration,
+    interval: Duration,
+}
 
-    fn handle_osc_messge(&self, maschine: &mut dyn Maschine, msg: &osc::Message) {
-        if msg.path.starts_with("/maschine/button") {
-            let btn = match osc_button_to_btn_map(&msg.path[17..]) {
-                Some(btn) => btn,
-                None => return,
-            };
+// how the pads play, switched between with --mode-button
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum DriverMode {
+    // each note lasts as long as its pad is held
+    Normal,
 
-            match msg.arguments.len() {
-                1 => maschine.set_button_light(
-                    btn,
-                    0xFFFFFF,
-                    match msg.arguments[0] {
-                        osc::Argument::i(val) => val as f32,
-                        osc::Argument::f(val) => val,
-                        _ => return,
-                    },
-                ),
+    // each note lasts the --one-shot length, however long its pad is held
+    OneShot,
 
-                2 => {
-                    if let (&osc::Argument::i(color), &osc::Argument::f(brightness)) =
-                        (&msg.arguments[0], &msg.arguments[1])
-                    {
-                        maschine.set_button_light(btn, (color as u32) & 0xFFFFFF, brightness);
-                    }
-                }
+    // a hit starts a note and the pad's next hit ends it
+    Latch,
+}
 
-                _ => return,
-            };
-        } else if msg.path.starts_with("/maschine/pad") {
-            match msg.arguments.len() {
-                3 => {
-                    if let (
-                        &osc::Argument::i(pad),
-                        &osc::Argument::i(color),
-                        &osc::Argument::f(brightness),
-                    ) = (&msg.arguments[0], &msg.arguments[1], &msg.arguments[2])
-                    {
-                        maschine.set_pad_light(
-                            pad as usize,
-                            (color as u32) & 0xFFFFFF,
-                            brightness as f32,
-                        );
-                    }
-                }
+// the grid --quantize holds pad hits back to, in steps of a beat at the tapped tempo
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Subdivision {
+    Quarter,
+    Eighth,
+    Sixteenth,
+    ThirtySecond,
+}
 
-                _ => return,
-            }
-        } else if msg.path.starts_with("/maschine/midi_note_base") {
-            match msg.arguments.len() {
-                1 => {
-                    if let osc::Argument::i(base) = msg.arguments[0] {
-                        maschine.set_midi_note_base(base as u8);
-                    }
-                }
-                _ => return,
-            }
+impl Subdivision {
+    fn steps_per_beat(self) -> u32 {
+        match self {
+            Subdivision::Quarter => 1,
+            Subdivision::Eighth => 2,
+            Subdivision::Sixteenth => 4,
+            Subdivision::ThirtySecond => 8,
         }
     }
 
-    fn send_osc_msg(&self, path: &str, arguments: Vec<osc::Argument>) {
-        let msg = osc::Message {
-            path: path,
-            arguments: arguments,
-        };
-
-        match self
-            .osc_socket
-            .send_to(&*msg.serialize().unwrap(), &self.osc_outgoing_addr)
-        {
-            Ok(_) => {}
-            Err(e) => println!(" :: error in send_to: {}", e),
+    pub fn from_name(name: &str) -> Option<Subdivision> {
+        match name {
+            "1/4" => Some(Subdivision::Quarter),
+            "1/8" => Some(Subdivision::Eighth),
+            "1/16" => Some(Subdivision::Sixteenth),
+            "1/32" => Some(Subdivision::ThirtySecond),
+            _ => None,
         }
     }
+}
 
-    fn send_osc_button_msg(
-        &mut self,
-        maschine: &mut dyn Maschine,
-        btn: MaschineButton,
-        status: usize,
-    ) {
-        let button = btn_to_osc_button_map(btn);
-        let controlbase = 40;
-        match button {
-            "play" => {
-                if status > 0 {
-                    let msg = Message::RPN7(Ch1, 1, status as u8);
-                    self.seq_port.send_message(&msg).unwrap();
-                    self.seq_handle.drain_output();
-                }
-            }
-            "stop" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 2, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "rec" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 3, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "grid" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 4, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "step_left" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 5, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "step_right" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 6, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "restart" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 7, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "browse" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 8, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "sampling" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 9, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "note_repeat" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 10, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "control" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 11, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "nav" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 12, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "nav_left" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 13, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "nav_right" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 14, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "main" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 15, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "scene" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 16, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "pattern" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 17, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "pad_mode" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 18, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "view" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 19, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "duplicate" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 20, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "select" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 21, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "solo" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 22, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "step" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 23, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "mute" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 24, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "navigate" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 25, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "tempo" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 26, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "enter" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 27, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "auto" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 28, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "all" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 29, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "f1" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 30, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "f2" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 31, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "f3" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 32, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "f4" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 33, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "f5" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 34, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "f6" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 35, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "f7" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 36, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "f8" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 37, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "page_right" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 38, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
-            "page_left" => {
-                if status > 0 {
-                let msg = Message::RPN7(Ch1, 39, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-                }
-            }
+// where pad and button input comes from
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum InputSource {
+    Hid,
 
-            "A8" => {
-                let msg = Message::RPN7(Ch1, controlbase, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-
-            "B5" => {
-                let msg = Message::RPN7(Ch1, controlbase + 1, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "B6" => {
-                let msg = Message::RPN7(Ch1, controlbase + 1, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "B7" => {
-                let msg = Message::RPN7(Ch1, controlbase + 1, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "B8" => {
-                let msg = Message::RPN7(Ch1, controlbase + 1, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "C8" => {
-                let msg = Message::RPN7(Ch1, controlbase + 1, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-
-            "D5" => {
-                let msg = Message::RPN7(Ch1, controlbase + 2, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "D6" => {
-                let msg = Message::RPN7(Ch1, controlbase + 2, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "D7" => {
-                let msg = Message::RPN7(Ch1, controlbase + 2, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "D8" => {
-                let msg = Message::RPN7(Ch1, controlbase + 2, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "E8" => {
-                let msg = Message::RPN7(Ch1, controlbase + 2, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-
-            "FF5" => {
-                let msg = Message::RPN7(Ch1, controlbase + 3, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "FF6" => {
-                let msg = Message::RPN7(Ch1, controlbase + 3, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "FF7" => {
-                let msg = Message::RPN7(Ch1, controlbase + 3, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "FF8" => {
-                let msg = Message::RPN7(Ch1, controlbase + 3, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "G8" => {
-                let msg = Message::RPN7(Ch1, controlbase + 3, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-
-            "H5" => {
-                let msg = Message::RPN7(Ch1, controlbase + 4, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "H6" => {
-                let msg = Message::RPN7(Ch1, controlbase + 4, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "H7" => {
-                let msg = Message::RPN7(Ch1, controlbase + 4, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "H8" => {
-                let msg = Message::RPN7(Ch1, controlbase + 4, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "I8" => {
-                let msg = Message::RPN7(Ch1, controlbase + 4, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-
-            "J5" => {
-                let msg = Message::RPN7(Ch1, controlbase + 5, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "J6" => {
-                let msg = Message::RPN7(Ch1, controlbase + 5, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "J7" => {
-                let msg = Message::RPN7(Ch1, controlbase + 5, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "J8" => {
-                let msg = Message::RPN7(Ch1, controlbase + 5, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "K8" => {
-                let msg = Message::RPN7(Ch1, controlbase + 5, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "L5" => {
-                let msg = Message::RPN7(Ch1, controlbase + 6, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "L6" => {
-                let msg = Message::RPN7(Ch1, controlbase + 6, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "L7" => {
-                let msg = Message::RPN7(Ch1, controlbase + 6, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "L8" => {
-                let msg = Message::RPN7(Ch1, controlbase + 6, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "M8" => {
-                let msg = Message::RPN7(Ch1, controlbase + 6, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "N5" => {
-                let msg = Message::RPN7(Ch1, controlbase + 7, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "N6" => {
-                let msg = Message::RPN7(Ch1, controlbase + 7, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "N7" => {
-                let msg = Message::RPN7(Ch1, controlbase + 7, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "N8" => {
-                let msg = Message::RPN7(Ch1, controlbase + 7, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "O8" => {
-                let msg = Message::RPN7(Ch1, controlbase + 7, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "P5" => {
-                let msg = Message::RPN7(Ch1, controlbase + 8, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "P6" => {
-                let msg = Message::RPN7(Ch1, controlbase + 8, status as u8);
-                self.seq_port.send_message(&msg).unwrap();
-                self.seq_handle.drain_output();
-            }
-            "group_a" => {
-                maschine.set_midi_note_base(24);
-            }
-            "group_b" => {
-                maschine.set_midi_note_base(36);
-            }
-            "group_c" => {
-                maschine.set_midi_note_base(48);
-            }
-            "group_d" => {
-                maschine.set_midi_note_base(60);
-            }
-            "group_e" => {
-                maschine.set_midi_note_base(72);
-            }
-            "group_f" => {
-                maschine.set_midi_note_base(84);
-            }
-            "group_g" => {
-                maschine.set_midi_note_base(96);
-            }
-            "group_h" => {
-                maschine.set_midi_note_base(108);
+    // the computer keyboard, for working on the driver without the hardware around
+    Keyboard,
+}
+
+#[derive(Clone)]
+enum PressureShape {
+    Linear,
+    Exponential(f32),
+    Constant(f32),
+
+    // velocities for 128 evenly spaced pressures, from --velocity-table
+    Table(Box<[U7; 128]>),
+}
+
+impl PressureShape {
+    fn velocity(&self, pressure: f32) -> U7 {
+        (match *self {
+            PressureShape::Linear => pressure,
+            PressureShape::Exponential(power) => pressure.powf(power),
+            PressureShape::Constant(c_pressure) => c_pressure,
+
+            PressureShape::Table(ref table) => {
+                // interpolated between the two nearest points
+                let pos = pressure.clamp(0.0, 1.0) * 127.0;
+                let idx = (pos as usize).min(126);
+                let frac = pos - idx as f32;
+
+                let low = table[idx] as f32;
+                let high = table[idx + 1] as f32;
+                return (low + (high - low) * frac).round() as U7;
             }
+        } * 127.0) as U7
+    }
+}
 
-            _ => {}
+// what pads use without --pressure or --velocity-table
+const DEFAULT_PRESSURE_SHAPE: PressureShape = PressureShape::Exponential(0.4);
+
+// a velocity table is 128 velocities separated by commas or whitespace, so a CSV row or
+// column works, as does a JSON array
+fn parse_velocity_table(text: &str) -> Result<Box<[U7; 128]>, String> {
+    let text = text.trim();
+    let text = text.strip_prefix('[').unwrap_or(text);
+    let text = text.strip_suffix(']').unwrap_or(text);
+
+    let mut table = Box::new([0 as U7; 128]);
+    let mut n = 0;
+
+    for field in text.split(|c: char| c == ',' || c.is_whitespace()) {
+        if field.is_empty() {
+            continue;
+        }
+
+        let vel = match field.parse::<U7>() {
+            Ok(vel @ 0..=127) => vel,
+            _ => return Err(format!("\"{}\" isn't a velocity between 0 and 127", field)),
+        };
+
+        if n == 128 {
+            return Err("more than 128 velocities".to_string());
+        }
+
+        if n > 0 && vel < table[n - 1] {
+            return Err(format!(
+                "velocity {} goes down, from {} to {}",
+                n,
+                table[n - 1],
+                vel
+            ));
         }
 
-        self.send_osc_msg(&*format!("/{}", button), osc_args![status as f32]);
+        table[n] = vel;
+        n += 1;
     }
 
-    fn send_osc_encoder_msg(&self, delta: i32) {
-        self.send_osc_msg("/maschine/encoder", osc_args![delta]);
+    if n != 128 {
+        return Err(format!("expected 128 velocities, got {}", n));
     }
+
+    Ok(table)
 }
 
-const PAD_NOTE_MAP: [U7; 16] = [12, 13, 14, 15, 8, 9, 10, 11, 4, 5, 6, 7, 0, 1, 2, 3];
+fn load_velocity_table(path: &str) -> Result<Box<[U7; 128]>, String> {
+    let text =
+        fs::read_to_string(path).map_err(|err| format!("couldn't read {}: {}", path, err))?;
+    parse_velocity_table(&text).map_err(|err| format!("{}: {}", path, err))
+}
 
-impl<'a> MaschineHandler for MHandler<'a> {
-    fn pad_pressed(&mut self, maschine: &mut dyn Maschine, pad_idx: usize, pressure: f32) {
-        let midi_note = maschine.get_midi_note_base() + PAD_NOTE_MAP[pad_idx];
-        let msg = Message::NoteOn(Ch1, midi_note, self.pressure_to_vel(pressure));
+// without --humanize-seed every run gets different numbers
+fn new_humanize(config: &Config, now: SystemTime) -> Humanize {
+    let seed = config.humanize_seed.unwrap_or_else(|| {
+        now.duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64
+    });
 
-        self.seq_port.send_message(&msg).unwrap();
-        self.seq_handle.drain_output();
+    Humanize::new(config.humanize.unwrap_or(0), seed)
+}
 
-        maschine.set_pad_light(pad_idx, self.pad_color(), pressure.sqrt());
+fn load_pressure_shape(config: &Config) -> Result<PressureShape, String> {
+    match config.velocity_table {
+        Some(ref path) => Ok(PressureShape::Table(load_velocity_table(path)?)),
+        None => Ok(config.pressure.clone().unwrap_or(DEFAULT_PRESSURE_SHAPE)),
     }
+}
 
-    fn pad_aftertouch(&mut self, maschine: &mut dyn Maschine, pad_idx: usize, pressure: f32) {
-        match self.pressure_shape {
-            PressureShape::Constant(_) => return,
-            _ => {}
-        }
+// pad hues for groups A to H, so the active note base can be told at a glance
+const GROUP_HUES: [f64; 8] = [0.0, 45.0, 90.0, 135.0, 180.0, 225.0, 270.0, 315.0];
+
+// built-in /maschine/theme palettes, each a gradient of hue, saturation and lightness from the
+// first pad to the last
+const THEMES: &[(&str, [f64; 3], [f64; 3])] = &[
+    ("sunset", [350.0, 1.0, 0.3], [45.0, 1.0, 0.35]),
+    ("ocean", [240.0, 1.0, 0.25], [170.0, 1.0, 0.3]),
+    ("forest", [140.0, 1.0, 0.2], [75.0, 1.0, 0.3]),
+    ("rainbow", [0.0, 1.0, 0.3], [337.5, 1.0, 0.3]),
+    ("ice", [200.0, 0.3, 0.5], [200.0, 1.0, 0.3]),
+];
+
+fn hsl([h, s, l]: [f64; 3]) -> HSL {
+    HSL { h, s, l }
+}
 
-        if !self.send_aftertouch {
-            return;
-        }
+fn hsl_to_rgb(color: HSL) -> u32 {
+    let (r, g, b) = color.to_rgb();
+
+    ((r as u32) << 16) | ((g as u32) << 8) | (b as u32)
+}
 
-        let midi_note = maschine.get_midi_note_base() + PAD_NOTE_MAP[pad_idx];
-        let msg = Message::PolyphonicPressure(Ch1, midi_note, self.pressure_to_vel(pressure));
+fn theme_gradient(from: HSL, to: HSL) -> [HSL; 16] {
+    let mut palette = [from; 16];
 
-        self.seq_port.send_message(&msg).unwrap();
-        self.seq_handle.drain_output();
+    for (pad, color) in palette.iter_mut().enumerate() {
+        let t = pad as f64 / 15.0;
 
-        maschine.set_pad_light(pad_idx, self.pad_color(), pressure.sqrt());
+        *color = HSL {
+            h: from.h + (to.h - from.h) * t,
+            s: from.s + (to.s - from.s) * t,
+            l: from.l + (to.l - from.l) * t,
+        };
     }
 
-    fn pad_released(&mut self, maschine: &mut dyn Maschine, pad_idx: usize) {
-        let midi_note = maschine.get_midi_note_base() + PAD_NOTE_MAP[pad_idx];
-        let msg = Message::NoteOff(Ch1, midi_note, 0);
-        self.seq_port.send_message(&msg).unwrap();
-        self.seq_handle.drain_output();
+    palette
+}
 
-        maschine.set_pad_light(pad_idx, self.pad_color(), PAD_RELEASED_BRIGHTNESS);
-    }
+// lowest note base of group A, each group after it is an octave higher
+const GROUP_NOTE_BASE: u8 = 24;
+
+const GROUP_BUTTONS: [MaschineButton; 8] = [
+    MaschineButton::GroupA,
+    MaschineButton::GroupB,
+    MaschineButton::GroupC,
+    MaschineButton::GroupD,
+    MaschineButton::GroupE,
+    MaschineButton::GroupF,
+    MaschineButton::GroupG,
+    MaschineButton::GroupH,
+];
+
+fn group_base(group: usize) -> u8 {
+    GROUP_NOTE_BASE + 12 * group as u8
+}
 
-    fn encoder_step(&mut self, _: &mut dyn Maschine, _: usize, delta: i32) {
-        self.send_osc_encoder_msg(delta);
-    }
+// with --group-color, how bright the group buttons that aren't selected are left, out of 1
+// like the pads. anything much dimmer rounds down to off
+const GROUP_UNSELECTED_BRIGHTNESS: f32 = 0.125;
+
+// how bright --toggle-buttons are left while they're off, out of 1
+const TOGGLE_OFF_BRIGHTNESS: f32 = 0.125;
+
+// how bright --mode-button buttons are left while their mode isn't the one the pads are in,
+// out of 1
+const MODE_OFF_BRIGHTNESS: f32 = 0.125;
+
+// past these the pads are either brighter than they're asked to be or barely light at all
+const MIN_LED_GAMMA: f32 = 1.0;
+const MAX_LED_GAMMA: f32 = 3.0;
+
+// how long one-shot notes last when switched to without --one-shot
+const DEFAULT_ONE_SHOT: Duration = Duration::from_millis(100);
+
+// what /maschine/mode can switch on and off
+const FEATURE_MODES: &[&str] = &[
+    "latch",
+    "one-shot",
+    "quantize",
+    "metronome",
+    "learn",
+    "led-follow",
+    "coalesce",
+];
+
+// what's done with OSC messages for paths we don't know
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum OscUnknown {
+    Ignore,
+    Log,
+
+    // logged, and sent back as /maschine/unknown with the path in front of the arguments
+    Echo,
+}
 
-    fn button_down(&mut self, maschine: &mut dyn Maschine, btn: MaschineButton, byte: u8) {
-        self.send_osc_button_msg(maschine, btn, byte as usize);
-    }
+// the kinds of outgoing OSC message --osc-repeat can send more than once
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum OscKind {
+    Button,
+    Encoder,
+    Pressure,
 
-    fn button_up(&mut self, maschine: &mut dyn Maschine, btn: MaschineButton, byte: u8) {
-        self.send_osc_button_msg(maschine, btn, byte as usize);
+    // everything else: the note base, tempo, learn, stats and so on
+    Other,
+}
+
+// button messages are the only ones outside of /maschine
+fn osc_kind(path: &str) -> OscKind {
+    if !path.starts_with("/maschine/") {
+        OscKind::Button
+    } else if path.starts_with("/maschine/encoder") {
+        OscKind::Encoder
+    } else if path.starts_with("/maschine/pad/") && path.ends_with("/pressure") {
+        OscKind::Pressure
+    } else {
+        OscKind::Other
     }
 }
 
-fn main() {
-    let args: Vec<_> = env::args().collect();
+// why an incoming OSC message was thrown away
+enum OscError {
+    UnknownPath,
+    BadArguments,
+}
 
-    if args.len() < 2 {
-        usage(&args[0]);
-        panic!("missing hidraw device path");
-    }
+// how long device input took to go out as MIDI, over the last LATENCY_WINDOW
+#[derive(Copy, Clone, Default)]
+struct LatencyStats {
+    count: u32,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
 
-    let dev_fd = match fcntl::open(
-        Path::new(&args[1]),
-        O_RDWR | O_NONBLOCK,
-        sys::stat::Mode::empty(),
-    ) {
-        Err(err) => panic!("couldn't open {}: {}", args[1], err.errno().desc()),
-        Ok(file) => file,
-    };
+// how many MIDI messages went out, and in how many writes to the sequencer, and how many
+// didn't fit even after one
+#[derive(Copy, Clone, Default)]
+struct MidiStats {
+    messages: usize,
+    drains: usize,
+    dropped: usize,
+}
 
-    let osc_socket = UdpSocket::bind("127.0.0.1:42434").unwrap();
-
-    let seq_handle = SequencerHandle::open("maschine.rs", HandleOpenStreams::Output).unwrap();
-    let seq_handle_in = SequencerHandle::open("maschine.rs", HandleOpenStreams::Input).unwrap();
-    let seq_port = seq_handle
-        .create_port(
-            "Pads MIDI",
-            PortCapabilities::PORT_CAPABILITY_READ | PortCapabilities::PORT_CAPABILITY_SUBS_READ,
-            PortType::MidiGeneric,
-        )
-        .unwrap();
-    let seq_port_in = seq_handle_in
-        .create_port(
-            "input",
-            PortCapabilities::PORT_CAPABILITY_READ | PortCapabilities::PORT_CAPABILITY_SUBS_WRITE,
-            PortType::MidiGeneric,
-        )
-        .unwrap();
-
-    let mut dev = devices::mk2::Mikro::new(dev_fd);
-
-    let mut handler = MHandler {
-        color: HSL {
-            h: 0.0,
-            s: 1.0,
-            l: 0.3,
-        },
+#[derive(Default)]
+struct OscStats {
+    bytes: usize,
+    received: usize,
+    handled: usize,
 
-        seq_port: &seq_port,
-        seq_handle: &seq_handle,
-        seq_port_in: &seq_port_in,
-        seq_handle_in: &seq_handle_in,
+    malformed: usize,
+    unknown_path: usize,
+    bad_arguments: usize,
+}
 
-        pressure_shape: PressureShape::Exponential(0.4),
-        send_aftertouch: false,
+struct MHandler<'a> {
+    // what /maschine/reset goes back to
+    config: Rc<Config>,
+    // what /maschine/reload builds the config from again
+    config_vars: Vec<(String, String)>,
+    config_args: Vec<String>,
+    clock: Rc<dyn Clock>,
+    default_pressure_shape: PressureShape,
+
+    group_colors: [HSL; 8],
+    // the group whose note base the pads are on, if they're on one
+    selected_group: Option<usize>,
+
+    // the --toggle-buttons that are on, each press flips one
+    toggled: Vec<MaschineButton>,
+    // the --auto-repeat buttons held down, with their status byte and when they fire next
+    repeating: Vec<(MaschineButton, u8, SystemTime)>,
+    mode: DriverMode,
+
+    // per-pad colours from /maschine/theme, over the colour and the group colours
+    palette: Option<[HSL; 16]>,
+
+    // set over /maschine/color, takes over from the group colors
+    color: Option<HSL>,
+    // released pads are lit in this instead, from --idle-color or /maschine/idle_color
+    idle_color: Option<HSL>,
+    idle_brightness: f32,
+    // with --dark-start, released pads stay off until the first pad has been played
+    dark: bool,
+
+    pad_light: PadLight,
+    pad_peaks: [f32; 16],
+
+    midi: &'a dyn MidiPorts,
+    // only polled for --midi-thru
+    midi_in_fd: Option<RawFd>,
 
-        osc_socket: &osc_socket,
-        osc_outgoing_addr: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 42435)),
-    };
+    pressure_shape: PressureShape,
+    // applied on top of it, from --velocity-scale or /maschine/velocity_scale
+    velocity_scale: f32,
+    humanize: Humanize,
+    // per pad, from /maschine/pad/<n>/aftertouch, all of them on with --aftertouch
+    send_aftertouch: [bool; 16],
+
+    note_range: NoteRangePolicy,
+
+    // the note each pad sent its note on for, so the note off matches it even if the note
+    // base changed in between
+    held_notes: [Option<U7>; 16],
+
+    // the same for buttons played with --button-note
+    held_button_notes: Vec<(MaschineButton, Channel, U7)>,
+
+    // while the --sustain-button is held, the notes let go of, ended when it's let go
+    sustain_held: bool,
+    sustained_notes: Vec<(Channel, U7)>,
+
+    // when each held note went on, so a pad whose release got lost doesn't hang forever
+    held_since: [Option<SystemTime>; 16],
+    max_hold: Option<Duration>,
+
+    // pads in the same choke group cut each other off, like open and closed hi-hats. for
+    // each group, the pad and note that last sounded in it
+    choke_groups: [Option<u8>; 16],
+    choke_sounding: [Option<(usize, U7)>; 16],
+
+    // when each pad hit over /maschine/trigger with a duration is due its note off
+    trigger_offs: [Option<SystemTime>; 16],
+    // the pads playing notes, oldest first, for --max-poly to steal from
+    voices: VecDeque<usize>,
+    // when each pad's --one-shot note ends, its release being ignored until then
+    one_shot_offs: [Option<SystemTime>; 16],
+    one_shot_length: Duration,
+
+    // pads flashed over /maschine/pad/<n>/flash, until when. nothing else lights them until
+    // then, and then they go back to what they'd otherwise show
+    pad_flashes: [Option<SystemTime>; 16],
+
+    // when set, encoder steps and aftertouch are held back and sent on the tick
+    coalesce: bool,
+    pending_encoder: i32,
+
+    // with --encoder-readout, the screen briefly shows the encoder's value as it's turned,
+    // labelled by the client over /maschine/encoder/label. what the screen showed before
+    // comes back once it's gone
+    encoder_readout: bool,
+    encoder_value: i32,
+    // where the list modes are in --encoder-values
+    encoder_index: usize,
+    // while it's pushed in, turns are scaled by --encoder-fine, and this carries what's left
+    // over from a step
+    encoder_held: bool,
+    encoder_fine_steps: f32,
+    screen_brightness: f32,
+    encoder_label: String,
+    encoder_units: String,
+    readout_until: Option<SystemTime>,
+    readout_saved: Option<Vec<u8>>,
+
+    // with --note-grid, the screen shows what note each pad plays, laid out like the pads
+    note_grid: bool,
+
+    // when the startup light test began, while it's still running
+    light_test_since: Option<SystemTime>,
+    pending_aftertouch: [Option<(f32, Option<SystemTime>)>; 16],
+    // with --osc-pressure, the latest raw pressure of each pad that changed since the last tick
+    pending_osc_pressure: [Option<f32>; 16],
+    // each held pad's pressure after --smoothing, starting from the hit
+    smoothed_pressure: [f32; 16],
+
+    // which of the --modifiers are held, a bit each in the order they were given
+    modifiers: u32,
+
+    // while shift is held, pads play these notes instead, if there are any
+    shift_layer: Option<[U7; 16]>,
+
+    // a note for each pad, in place of the note base, from --pad-notes or /maschine/pad_notes
+    pad_notes: Option<[U7; 16]>,
+    // from --pad-channels, or all of them on the one from /maschine/channel
+    pad_channels: [Channel; 16],
+
+    debounce: Debounce,
+    release_lockout: ReleaseLockout,
+
+    // light buttons up while they're held, rather than leaving their LEDs to OSC
+    led_follow: bool,
+
+    // report what's touched instead of playing it, for working out mappings
+    learn: bool,
+
+    // in BPM, set by tapping the tempo button
+    tempo: f32,
+    tempo_taps: VecDeque<SystemTime>,
+
+    // where the --quantize grid is counted from, the last tap lands on a beat
+    beat_origin: SystemTime,
+    // how long each pad's last hit was held back, its note off waits as long
+    quantize: Option<Subdivision>,
+    quantize_delay: [Duration; 16],
+    quantized: Vec<(SystemTime, Message)>,
+
+    // pad note ons sent since the last tick, and those held back by --roll-limit, along with
+    // any note offs that have to follow them
+    note_ons_this_tick: usize,
+    rolled: VecDeque<Message>,
+
+    // the metronome clicks on the beats counted from beat_origin, this is the last one clicked
+    metronome: bool,
+    metronome_beat: Option<u64>,
 
-    dev.clear_screen();
+    osc_socket: &'a UdpSocket,
+    osc_outgoing_addrs: Vec<SocketAddr>,
+    osc_stats: OscStats,
+
+    // when the device report being handled arrived, None while handling anything else
+    input_at: Option<SystemTime>,
+    latency: Cell<LatencyStats>,
+    latency_since: SystemTime,
+
+    // messages sent since the sequencer's output was last drained, which unless
+    // --no-midi-batch happens once a pass through the event loop
+    midi_unsent: Cell<bool>,
+    midi_stats: Cell<MidiStats>,
+}
 
-    //Trying to draw stuff here
-    if args.len() < 3 {
-        dev.write_screen();
+impl<'a> MHandler<'a> {
+    fn new(
+        config: Rc<Config>,
+        config_vars: Vec<(String, String)>,
+        config_args: Vec<String>,
+        pressure_shape: PressureShape,
+        clock: Rc<dyn Clock>,
+        midi: &'a dyn MidiPorts,
+        osc_socket: &'a UdpSocket,
+    ) -> Self {
+        MHandler {
+            config: config.clone(),
+            config_vars,
+            config_args,
+            clock: clock.clone(),
+            default_pressure_shape: pressure_shape.clone(),
+
+            group_colors: GROUP_HUES.map(|h| HSL { h, s: 1.0, l: 0.3 }),
+            selected_group: None,
+            toggled: vec![],
+            repeating: vec![],
+            mode: config.start_mode(),
+            palette: None,
+            color: None,
+            idle_color: config.idle_color,
+            idle_brightness: if config.dark_start {
+                0.0
+            } else {
+                config.idle_brightness
+            },
+            dark: config.dark_start,
+
+            pad_light: config.pad_light,
+            pad_peaks: [0.0; 16],
+
+            midi,
+            midi_in_fd: if config.midi_thru.is_empty() {
+                None
+            } else {
+                midi.input_fd()
+            },
+
+            pressure_shape,
+            velocity_scale: config.velocity_scale,
+            humanize: new_humanize(&config, clock.now()),
+            send_aftertouch: [config.aftertouch.is_some(); 16],
+
+            note_range: config.note_range,
+            held_notes: [None; 16],
+            held_button_notes: vec![],
+            sustain_held: false,
+            sustained_notes: vec![],
+            held_since: [None; 16],
+            max_hold: config.max_hold,
+            choke_groups: config.choke_groups,
+            choke_sounding: [None; 16],
+            trigger_offs: [None; 16],
+            voices: VecDeque::new(),
+            one_shot_offs: [None; 16],
+            one_shot_length: config.one_shot.unwrap_or(DEFAULT_ONE_SHOT),
+            pad_flashes: [None; 16],
+
+            coalesce: config.coalesce,
+            pending_encoder: 0,
+
+            // there's nowhere to show it
+            encoder_readout: config.encoder_readout && config.screen,
+            encoder_value: 0,
+            encoder_index: 0,
+            encoder_held: false,
+            encoder_fine_steps: 0.0,
+            screen_brightness: 1.0,
+            encoder_label: "ENCODER".to_string(),
+            encoder_units: String::new(),
+            readout_until: None,
+            readout_saved: None,
+
+            note_grid: config.note_grid && config.screen,
+
+            light_test_since: None,
+            pending_aftertouch: [None; 16],
+            pending_osc_pressure: [None; 16],
+            smoothed_pressure: [0.0; 16],
+
+            modifiers: 0,
+            shift_layer: config.shift_layer,
+
+            pad_notes: config.pad_notes,
+            pad_channels: config.pad_channels,
+
+            debounce: Debounce::new(config.debounce),
+            release_lockout: ReleaseLockout::new(config.release_lockout),
+
+            led_follow: config.led_follow,
+            learn: config.learn,
+
+            tempo: DEFAULT_TEMPO,
+            tempo_taps: VecDeque::with_capacity(TEMPO_TAPS),
+
+            beat_origin: clock.now(),
+            quantize: config.quantize,
+            quantize_delay: [Duration::ZERO; 16],
+            quantized: vec![],
+            note_ons_this_tick: 0,
+            rolled: VecDeque::new(),
+
+            metronome: config.metronome,
+            metronome_beat: None,
+
+            osc_socket,
+            osc_outgoing_addrs: if config.self_test {
+                vec![]
+            } else {
+                config.osc_destinations()
+            },
+            osc_stats: OscStats::default(),
+
+            input_at: None,
+            latency: Cell::new(LatencyStats::default()),
+            midi_unsent: Cell::new(false),
+            midi_stats: Cell::new(MidiStats::default()),
+            latency_since: clock.now(),
+        }
     }
-    for i in 0..16 {
-        dev.set_pad_light(i, handler.pad_color(), PAD_RELEASED_BRIGHTNESS);
+
+    // the colour set over OSC, or else the active group's
+    fn base_color(&self, maschine: &dyn Maschine) -> HSL {
+        match self.color {
+            Some(color) => color,
+            None => {
+                let base = maschine.get_midi_note_base().max(GROUP_NOTE_BASE);
+                let group = ((base - GROUP_NOTE_BASE) / 12).min(7);
+
+                self.group_colors[group as usize]
+            }
+        }
+    }
+
+    fn pad_color(&self, maschine: &dyn Maschine, pad_idx: usize) -> u32 {
+        match self.palette {
+            Some(ref palette) => hsl_to_rgb(palette[pad_idx]),
+            None => hsl_to_rgb(self.base_color(maschine)),
+        }
+    }
+
+    fn pressure_to_vel(&self, pressure: f32) -> U7 {
+        self.pressure_shape.velocity(pressure)
+    }
+
+    // --velocity-scale, for hitting a synth harder or softer overall. a velocity that wasn't 0
+    // doesn't become one, a note on would take it for a note off
+    fn scale_velocity(&self, velocity: U7) -> U7 {
+        if velocity == 0 {
+            return 0;
+        }
+
+        let scaled = velocity as f32 * self.velocity_scale;
+        scaled.round().clamp(1.0, 127.0) as U7
+    }
+
+    // what's shown on a freshly opened device
+    fn init_device(&mut self, maschine: &mut dyn Maschine) {
+        if self.config.screen {
+            // there's no knowing what a kept screen shows, so the first thing drawn on it
+            // goes out whole
+            if self.config.keep_screen {
+                maschine.force_full_redraw();
+            } else {
+                maschine.clear_screen();
+            }
+
+            //Trying to draw stuff here
+            if self.note_grid {
+                self.draw_note_grid(maschine);
+            } else if self.config.draw_picture && !self.config.keep_screen {
+                maschine.write_screen();
+            }
+        }
+
+        self.update_pad_colors(maschine);
+
+        let base = maschine.get_midi_note_base();
+        self.selected_group = (0..8).find(|&group| group_base(group) == base);
+        self.update_group_lights(maschine);
+
+        for &btn in &self.config.toggle_buttons {
+            self.update_toggle_light(maschine, btn);
+        }
+
+        self.update_mode_lights(maschine);
+    }
+
+    // everything back to how it was at startup
+    fn reset(&mut self, maschine: &mut dyn Maschine) {
+        // hits still held back never get played, their note offs make no difference
+        self.rolled.clear();
+        self.one_shot_offs = [None; 16];
+
+        for pad_idx in 0..16 {
+            if self.held_notes[pad_idx].is_some() {
+                self.release_pad(maschine, pad_idx);
+            }
+        }
+        self.release_button_notes();
+        self.release_sustained_notes();
+
+        self.color = None;
+        self.palette = None;
+        self.idle_color = self.config.idle_color;
+        self.pad_notes = self.config.pad_notes;
+        self.pad_channels = self.config.pad_channels;
+        self.send_aftertouch = [self.config.aftertouch.is_some(); 16];
+        self.dark = self.config.dark_start;
+        self.idle_brightness = if self.dark {
+            0.0
+        } else {
+            self.config.idle_brightness
+        };
+        self.pressure_shape = self.default_pressure_shape.clone();
+        self.velocity_scale = self.config.velocity_scale;
+
+        self.choke_sounding = [None; 16];
+        self.trigger_offs = [None; 16];
+        self.pad_flashes = [None; 16];
+        self.toggled.clear();
+        self.repeating.clear();
+        self.mode = self.config.start_mode();
+        self.one_shot_length = self.config.one_shot.unwrap_or(DEFAULT_ONE_SHOT);
+        self.coalesce = self.config.coalesce;
+        self.led_follow = self.config.led_follow;
+        self.learn = self.config.learn;
+        self.pending_encoder = 0;
+        self.encoder_value = 0;
+        self.encoder_index = 0;
+        self.encoder_fine_steps = 0.0;
+        if self.config.encoder_mode == EncoderMode::Screen {
+            self.set_screen_brightness(maschine, 1.0);
+        }
+
+        self.tempo = DEFAULT_TEMPO;
+        self.tempo_taps.clear();
+
+        self.metronome = self.config.metronome;
+        self.metronome_beat = None;
+
+        // notes that were already held back still go out, so nothing is left hanging
+        self.send_quantized(None);
+        self.quantize = self.config.quantize;
+        self.quantize_delay = [Duration::ZERO; 16];
+
+        // the screen gets redrawn from scratch anyway
+        self.readout_until = None;
+        self.readout_saved = None;
+        self.light_test_since = None;
+
+        maschine.set_midi_note_base(DEFAULT_NOTE_BASE);
+        maschine.set_led_fade(self.config.led_fade);
+        maschine.set_led_gamma(self.config.led_gamma);
+        maschine.clear_lights();
+        self.init_device(maschine);
+
+        println!(" :: reset");
+    }
+
+    // builds the config again from the same command line and environment, picking up whatever
+    // has changed in the --config file, and starts over on it as /maschine/reset would. one
+    // that doesn't parse or has errors is turned down and the old one stays
+    fn reload(&mut self, maschine: &mut dyn Maschine) {
+        let config = match Config::from_env_and_args(&self.config_vars, &self.config_args) {
+            Ok(config) => config,
+            Err(err) => {
+                println!(" :: reload: {}, keeping the old config", err);
+                return;
+            }
+        };
+
+        let mut errors = 0;
+        for problem in config.validate() {
+            match problem {
+                Problem::Error(err) => {
+                    println!(" :: reload: config error: {}", err);
+                    errors += 1;
+                }
+                Problem::Warning(warning) => println!(" :: reload: config warning: {}", warning),
+            }
+        }
+
+        if errors > 0 {
+            println!(
+                " :: reload: {} config errors, keeping the old config",
+                errors
+            );
+            return;
+        }
+
+        let pressure_shape = match load_pressure_shape(&config) {
+            Ok(shape) => shape,
+            Err(err) => {
+                println!(" :: reload: {}, keeping the old config", err);
+                return;
+            }
+        };
+
+        // the held notes end on the channels they started on, before those can change
+        self.rolled.clear();
+        self.one_shot_offs = [None; 16];
+        for pad_idx in 0..16 {
+            if self.held_notes[pad_idx].is_some() {
+                self.release_pad(maschine, pad_idx);
+            }
+        }
+        self.release_button_notes();
+        self.release_sustained_notes();
+        self.send_quantized(None);
+
+        self.config = Rc::new(config);
+        self.default_pressure_shape = pressure_shape;
+
+        // what only gets set at startup otherwise. the device, the screen and the MIDI and
+        // OSC ports stay as they are until a restart
+        self.pad_light = self.config.pad_light;
+        self.note_range = self.config.note_range;
+        self.max_hold = self.config.max_hold;
+        self.choke_groups = self.config.choke_groups;
+        self.encoder_readout = self.config.encoder_readout && self.config.screen;
+        self.note_grid = self.config.note_grid && self.config.screen;
+        self.shift_layer = self.config.shift_layer;
+        self.debounce = Debounce::new(self.config.debounce);
+        self.release_lockout = ReleaseLockout::new(self.config.release_lockout);
+        self.humanize = new_humanize(&self.config, self.clock.now());
+        if !self.config.self_test {
+            self.osc_outgoing_addrs = self.config.osc_destinations();
+        }
+
+        self.reset(maschine);
+
+        println!(" :: reloaded the config");
+    }
+
+    // ends everything that could be sounding without touching the device, for when it's gone:
+    // note offs for the held pads, then all notes off on every channel we play on in case
+    // something was missed
+    fn all_notes_off(&mut self) {
+        self.rolled.clear();
+        self.one_shot_offs = [None; 16];
+        self.send_quantized(None);
+
+        for pad_idx in 0..16 {
+            if let Some(midi_note) = self.held_notes[pad_idx].take() {
+                let channel = self.pad_channels[pad_idx];
+                self.send_osc_note(channel, midi_note, 0, false);
+                self.send_midi(&Message::NoteOff(channel, midi_note, 0));
+            }
+        }
+        self.release_button_notes();
+        self.release_sustained_notes();
+
+        let mut channels: Vec<Channel> = self.pad_channels.to_vec();
+        channels.extend(
+            self.config
+                .button_notes
+                .iter()
+                .map(|(button, _, _)| self.config.button_channel(button)),
+        );
+        channels.sort();
+        channels.dedup();
+
+        for channel in channels {
+            self.send_midi(&Message::AllNotesOff(channel));
+        }
+    }
+
+    // a step of the startup light test, then back to the normal lights once it's over
+    fn light_test(&mut self, maschine: &mut dyn Maschine, since: SystemTime) {
+        let elapsed = self.clock.since(since);
+        let step = (elapsed.as_millis() / LIGHT_TEST_STEP.as_millis()) as usize;
+
+        if step >= 16 + LIGHT_TEST_BUTTONS.len() {
+            self.light_test_since = None;
+
+            // only what the test lit, so anything set over OSC in the meantime stays
+            for &btn in LIGHT_TEST_BUTTONS.iter() {
+                maschine.set_button_light(btn, 0, 0.0);
+            }
+
+            self.update_pad_colors(maschine);
+            return;
+        }
+
+        for pad_idx in 0..16.min(step + 1) {
+            let color = hsl_to_rgb(HSL {
+                h: pad_idx as f64 * 22.5,
+                s: 1.0,
+                l: 0.3,
+            });
+
+            maschine.set_pad_light(pad_idx, color, 1.0);
+        }
+
+        for &btn in LIGHT_TEST_BUTTONS.iter().take(step.saturating_sub(15)) {
+            maschine.set_button_light(btn, 0xFFFFFF, button_level(1.0));
+        }
+    }
+
+    // the brightness of a held pad, raising its peak along the way
+    fn held_pad_brightness(&mut self, pad_idx: usize, pressure: f32) -> f32 {
+        let brightness = pressure.sqrt();
+
+        match self.pad_light {
+            PadLight::Follow => brightness,
+            PadLight::Peak => {
+                self.pad_peaks[pad_idx] = self.pad_peaks[pad_idx].max(brightness);
+                self.pad_peaks[pad_idx]
+            }
+        }
+    }
+
+    // what a released pad shows, the colour it's hit in unless --idle-color says otherwise
+    fn idle_pad_color(&self, maschine: &dyn Maschine, pad_idx: usize) -> u32 {
+        match self.idle_color {
+            Some(color) => hsl_to_rgb(color),
+            None => self.pad_color(maschine, pad_idx),
+        }
+    }
+
+    fn update_pad_colors(&self, maschine: &mut dyn Maschine) {
+        for i in 0..16 {
+            let (color, brightness) = match maschine.get_pad_pressure(i).unwrap() {
+                b if b == 0.0 => (self.idle_pad_color(maschine, i), self.idle_brightness),
+                pressure @ _ => (
+                    self.pad_color(maschine, i),
+                    match self.pad_light {
+                        PadLight::Follow => pressure.sqrt(),
+                        PadLight::Peak => self.pad_peaks[i].max(pressure.sqrt()),
+                    },
+                ),
+            };
+
+            self.light_pad(maschine, i, color, brightness);
+        }
+    }
+
+    // leaves a flashing pad alone until its flash is over
+    fn light_pad(&self, maschine: &mut dyn Maschine, pad_idx: usize, color: u32, brightness: f32) {
+        if self.pad_flashes[pad_idx].is_none() {
+            maschine.set_pad_light(pad_idx, color, brightness);
+        }
+    }
+
+    // cuts off whatever's sounding in the choke group, then makes the pad the one sounding
+    fn choke(&mut self, group: usize, pad_idx: usize, note: Option<U7>) {
+        if let Some((choked_pad, choked_note)) = self.choke_sounding[group].take() {
+            // a pad that's still held shouldn't send a second note off when let go
+            if self.held_notes[choked_pad] == Some(choked_note) {
+                self.held_notes[choked_pad] = None;
+                self.held_since[choked_pad] = None;
+            }
+
+            // it ends as the note cutting it off starts, sustained or not
+            let channel = self.pad_channels[choked_pad];
+            self.sustained_notes
+                .retain(|&n| n != (channel, choked_note));
+            if ends_note(
+                &self.held_notes,
+                &self.pad_channels,
+                channel,
+                choked_note,
+                self.config.shared_notes,
+            ) {
+                self.send_note_off(self.quantize_delay[pad_idx], channel, choked_note);
+            }
+        }
+
+        self.choke_sounding[group] = note.map(|note| (pad_idx, note));
+    }
+
+    fn set_note_base(&mut self, maschine: &mut dyn Maschine, base: u8) {
+        maschine.set_midi_note_base(base);
+        self.update_pad_colors(maschine);
+
+        // moving off a group's base by other means leaves no group selected
+        if let Some(group) = self.selected_group {
+            if group_base(group) != base {
+                self.selected_group = None;
+                self.update_group_lights(maschine);
+            }
+        }
+
+        if self.note_grid {
+            self.draw_note_grid(maschine);
+        }
+    }
+
+    // the note a hit on the pad would play right now
+    fn current_pad_note(&self, maschine: &dyn Maschine, pad_idx: usize) -> Option<U7> {
+        match (self.shift_layer, self.pad_notes) {
+            (Some(ref layer), _) if self.modifier_held(MaschineButton::Shift) => {
+                Some(layer[pad_idx])
+            }
+            (_, Some(ref notes)) => Some(notes[pad_idx]),
+            _ => pad_note(maschine.get_midi_note_base(), pad_idx, self.note_range),
+        }
+    }
+
+    fn draw_note_grid(&mut self, maschine: &mut dyn Maschine) {
+        let names: Vec<String> = (0..16)
+            .map(|pad_idx| match self.current_pad_note(maschine, pad_idx) {
+                Some(note) => note_name(note),
+                None => "--".to_string(),
+            })
+            .collect();
+
+        let draw = |screen: &mut [u8]| {
+            screen::clear_rect(screen, 0, 0, SCREEN_WIDTH, SCREEN_HEIGHT);
+
+            for (pad_idx, name) in names.iter().enumerate() {
+                let x = (pad_idx % 4) * NOTE_GRID_CELL_WIDTH + 4;
+                let y = (pad_idx / 4) * NOTE_GRID_CELL_HEIGHT + 1;
+                screen::draw_text(screen, x, y, 2, name);
+            }
+        };
+
+        // under the encoder readout, the grid is what comes back once it's gone
+        match self.readout_saved {
+            Some(ref mut saved) => draw(saved),
+            None => {
+                draw(maschine.get_screen_mut());
+                maschine.send_screen();
+            }
+        }
+    }
+
+    fn select_group(&mut self, maschine: &mut dyn Maschine, group: usize) {
+        self.select_group_base(maschine, group_base(group));
+
+        self.selected_group = Some(group);
+        self.update_group_lights(maschine);
+    }
+
+    // with --group-color, the selected group's button lit and the rest dimmed. without it the
+    // group buttons are left to OSC
+    fn update_group_lights(&self, maschine: &mut dyn Maschine) {
+        let color = match self.config.group_color {
+            Some(color) => color,
+            None => return,
+        };
+
+        for (group, &btn) in GROUP_BUTTONS.iter().enumerate() {
+            let brightness = if self.selected_group == Some(group) {
+                1.0
+            } else {
+                GROUP_UNSELECTED_BRIGHTNESS
+            };
+
+            maschine.set_button_light(btn, color, button_level(brightness));
+        }
+    }
+
+    fn update_mode_lights(&self, maschine: &mut dyn Maschine) {
+        for &(btn, mode) in &self.config.mode_buttons {
+            let brightness = if mode == self.mode {
+                1.0
+            } else {
+                MODE_OFF_BRIGHTNESS
+            };
+            maschine.set_button_light(btn, 0xFFFFFF, button_level(brightness));
+        }
+    }
+
+    // the notes playing in the old mode are ended, as the new one may not end them
+    fn set_mode(&mut self, maschine: &mut dyn Maschine, mode: DriverMode) {
+        if mode == self.mode {
+            return;
+        }
+
+        self.one_shot_offs = [None; 16];
+        for pad_idx in 0..16 {
+            if self.held_notes[pad_idx].is_some() {
+                self.release_pad(maschine, pad_idx);
+            }
+        }
+
+        self.mode = mode;
+        self.update_mode_lights(maschine);
+    }
+
+    // /maschine/mode. latch and one-shot are driver modes, so turning one on leaves the other
+    fn set_feature_mode(
+        &mut self,
+        maschine: &mut dyn Maschine,
+        name: &str,
+        on: bool,
+        params: &[osc::Argument],
+    ) -> Result<(), OscError> {
+        match (name, params) {
+            ("latch", []) => {
+                if on {
+                    self.set_mode(maschine, DriverMode::Latch);
+                } else if self.mode == DriverMode::Latch {
+                    self.set_mode(maschine, DriverMode::Normal);
+                }
+            }
+            ("one-shot", params) => {
+                match *params {
+                    [] => {}
+                    [osc::Argument::i(ms)] if ms > 0 => {
+                        self.one_shot_length = Duration::from_millis(ms as u64)
+                    }
+                    _ => return Err(OscError::BadArguments),
+                }
+
+                if on {
+                    self.set_mode(maschine, DriverMode::OneShot);
+                } else if self.mode == DriverMode::OneShot {
+                    self.set_mode(maschine, DriverMode::Normal);
+                }
+            }
+            ("quantize", params) => {
+                let subdivision = match *params {
+                    [] => self.config.quantize.unwrap_or(Subdivision::Sixteenth),
+                    [osc::Argument::s(q)] => match Subdivision::from_name(q) {
+                        Some(subdivision) => subdivision,
+                        None => return Err(OscError::BadArguments),
+                    },
+                    _ => return Err(OscError::BadArguments),
+                };
+
+                self.quantize = if on { Some(subdivision) } else { None };
+            }
+            ("metronome", []) => {
+                self.metronome = on;
+                self.metronome_beat = None;
+            }
+            ("learn", []) => self.learn = on,
+            ("led-follow", []) => self.led_follow = on,
+            ("coalesce", []) => {
+                // what's been held back goes out before it'd be left waiting for nothing
+                if !on {
+                    self.flush_coalesced(maschine);
+                }
+                self.coalesce = on;
+            }
+            (name, _) if FEATURE_MODES.contains(&name) => return Err(OscError::BadArguments),
+            (name, _) => {
+                println!(
+                    " :: /maschine/mode: there's no mode called \"{}\" (try {})",
+                    name,
+                    FEATURE_MODES.join(", ")
+                );
+                return Ok(());
+            }
+        }
+
+        println!(" :: {} {}", name, if on { "on" } else { "off" });
+        Ok(())
+    }
+
+    // the held pads' notes end on the channels they were played on first, or they'd hang
+    fn set_pad_channel(&mut self, maschine: &mut dyn Maschine, channel: Channel) {
+        self.one_shot_offs = [None; 16];
+        for pad_idx in 0..16 {
+            if self.held_notes[pad_idx].is_some() {
+                self.release_pad(maschine, pad_idx);
+            }
+        }
+
+        self.pad_channels = [channel; 16];
+        println!(" :: pads on channel {}", channel as u8 + 1);
+    }
+
+    fn update_toggle_light(&self, maschine: &mut dyn Maschine, btn: MaschineButton) {
+        if self.toggled.contains(&btn) {
+            let color = self.config.toggle_on_color;
+            maschine.set_button_light(btn, color, button_level(1.0));
+        } else {
+            let color = self.config.toggle_off_color;
+            maschine.set_button_light(btn, color, button_level(TOGGLE_OFF_BRIGHTNESS));
+        }
+    }
+
+    // like set_note_base, but for changes made on the hardware, which OSC clients have no
+    // other way of hearing about
+    fn select_group_base(&mut self, maschine: &mut dyn Maschine, base: u8) {
+        if maschine.get_midi_note_base() == base {
+            return;
+        }
+
+        self.set_note_base(maschine, base);
+        self.send_osc_msg("/maschine/midi_note_base", osc_args![base as i32]);
+    }
+
+    // passes whatever arrived on the input port through to the output port, for the types
+    // asked for
+    fn recv_midi_msg(&mut self) {
+        self.midi.pass_thru(&self.config.midi_thru);
+    }
+
+    fn recv_osc_msg(&mut self, maschine: &mut dyn Maschine) {
+        let mut buf = [0u8; udp::OSC_RECV_BUFFER];
+
+        let (nbytes, msg) = match udp::recv_osc(self.osc_socket, &mut buf) {
+            Ok(received) => received,
+            Err(e) => {
+                println!(" :: error in recv_from(): {}", e);
+                return;
+            }
+        };
+
+        self.osc_stats.bytes += nbytes;
+        self.osc_stats.received += 1;
+
+        let msg = match msg {
+            Some(msg) => msg,
+            None => {
+                self.osc_stats.malformed += 1;
+                println!(" :: couldn't decode OSC message :c");
+                return;
+            }
+        };
+
+        match self.handle_osc_messge(maschine, &msg) {
+            Ok(()) => self.osc_stats.handled += 1,
+            Err(OscError::UnknownPath) => {
+                self.osc_stats.unknown_path += 1;
+                self.report_unknown_path(&msg);
+            }
+            Err(OscError::BadArguments) => self.osc_stats.bad_arguments += 1,
+        }
+    }
+
+    // with --osc-unknown, so that a typo in a client's path doesn't just vanish
+    fn report_unknown_path(&self, msg: &osc::Message) {
+        match self.config.osc_unknown {
+            OscUnknown::Ignore => return,
+            OscUnknown::Log | OscUnknown::Echo => {
+                println!(" :: unknown OSC path {} {:?}", msg.path, msg.arguments)
+            }
+        }
+
+        if self.config.osc_unknown == OscUnknown::Echo {
+            let mut arguments = vec![osc::Argument::s(msg.path)];
+
+            for arg in msg.arguments.iter() {
+                arguments.push(match *arg {
+                    osc::Argument::i(i) => osc::Argument::i(i),
+                    osc::Argument::f(f) => osc::Argument::f(f),
+                    osc::Argument::s(s) => osc::Argument::s(s),
+                    osc::Argument::T => osc::Argument::T,
+                    osc::Argument::F => osc::Argument::F,
+                    osc::Argument::None => osc::Argument::None,
+                });
+            }
+
+            self.send_osc_msg("/maschine/unknown", arguments);
+        }
+    }
+
+    fn handle_osc_messge(
+        &mut self,
+        maschine: &mut dyn Maschine,
+        msg: &osc::Message,
+    ) -> Result<(), OscError> {
+        let pressed_name = osc_button_name(msg.path).and_then(|name| name.strip_suffix("/press"));
+
+        if let Some(name) = pressed_name {
+            let btn = match osc_button_to_btn_map(name) {
+                Some(btn) => btn,
+                None => return Err(OscError::UnknownPath),
+            };
+
+            // the same bytes the keyboard device sends. no argument is a press and a release
+            match *msg.arguments {
+                [] => {
+                    self.button_down(maschine, btn, 1);
+                    self.button_up(maschine, btn, 0);
+                }
+                [osc::Argument::i(0)] => self.button_up(maschine, btn, 0),
+                [osc::Argument::i(_)] => self.button_down(maschine, btn, 1),
+                _ => return Err(OscError::BadArguments),
+            }
+        } else if msg.path.starts_with("/maschine/button") {
+            let btn = match osc_button_name(msg.path).and_then(osc_button_to_btn_map) {
+                Some(btn) => btn,
+                None => return Err(OscError::UnknownPath),
+            };
+
+            match msg.arguments.len() {
+                0 => return Err(OscError::BadArguments),
+
+                1 => maschine.set_button_light(
+                    btn,
+                    0xFFFFFF,
+                    match msg.arguments[0] {
+                        osc::Argument::i(val) => val as f32,
+                        osc::Argument::f(val) => val,
+                        _ => return Err(OscError::BadArguments),
+                    },
+                ),
+
+                n => {
+                    let color = match parse_color(&msg.arguments[..n - 1]) {
+                        Some(color) => color,
+                        None => return Err(OscError::BadArguments),
+                    };
+
+                    match msg.arguments[n - 1] {
+                        osc::Argument::f(brightness) => {
+                            maschine.set_button_light(btn, color, brightness)
+                        }
+                        _ => return Err(OscError::BadArguments),
+                    }
+                }
+            };
+        } else if msg.path == "/maschine/pad_notes" {
+            // no arguments goes back to the note base
+            self.pad_notes = if msg.arguments.is_empty() {
+                None
+            } else if msg.arguments.len() == 16 {
+                let mut notes = [0 as U7; 16];
+                for (note, arg) in notes.iter_mut().zip(msg.arguments.iter()) {
+                    *note = match *arg {
+                        osc::Argument::i(n @ 0..=127) => n as U7,
+                        _ => return Err(OscError::BadArguments),
+                    };
+                }
+
+                Some(notes)
+            } else {
+                return Err(OscError::BadArguments);
+            };
+
+            if self.note_grid {
+                self.draw_note_grid(maschine);
+            }
+        } else if let Some(pad) = osc_pad_path(msg.path, "aftertouch") {
+            self.send_aftertouch[pad] = match *msg.arguments {
+                [osc::Argument::i(on)] => on != 0,
+                _ => return Err(OscError::BadArguments),
+            };
+
+            // nothing held back for the tick goes out once it's been turned off
+            if !self.send_aftertouch[pad] {
+                self.pending_aftertouch[pad] = None;
+            }
+        } else if let Some(pad) = osc_pad_path(msg.path, "flash") {
+            let n = msg.arguments.len();
+            if n < 2 {
+                return Err(OscError::BadArguments);
+            }
+
+            let color = match parse_color(&msg.arguments[..n - 1]) {
+                Some(color) => color,
+                None => return Err(OscError::BadArguments),
+            };
+
+            let duration = match msg.arguments[n - 1] {
+                osc::Argument::i(ms) if ms > 0 => Duration::from_millis(ms as u64),
+                _ => return Err(OscError::BadArguments),
+            };
+
+            maschine.set_pad_light(pad, color, 1.0);
+            self.pad_flashes[pad] = Some(self.clock.now() + duration);
+        } else if msg.path.starts_with("/maschine/pad") {
+            let n = msg.arguments.len();
+            if n < 3 {
+                return Err(OscError::BadArguments);
+            }
+
+            let pad = match msg.arguments[0] {
+                osc::Argument::i(pad @ 0..=15) => pad as usize,
+                _ => return Err(OscError::BadArguments),
+            };
+
+            let color = match parse_color(&msg.arguments[1..n - 1]) {
+                Some(color) => color,
+                None => return Err(OscError::BadArguments),
+            };
+
+            match msg.arguments[n - 1] {
+                osc::Argument::f(brightness) => maschine.set_pad_light(pad, color, brightness),
+                _ => return Err(OscError::BadArguments),
+            }
+
+            // what's set by hand isn't undone when a flash runs out
+            self.pad_flashes[pad] = None;
+        } else if msg.path == "/maschine/color" {
+            // no arguments hands the pads back to the group colors
+            self.color = if msg.arguments.is_empty() {
+                None
+            } else {
+                match parse_hsl(&msg.arguments) {
+                    Some(color) => Some(color),
+                    None => return Err(OscError::BadArguments),
+                }
+            };
+
+            self.palette = None;
+            self.update_pad_colors(maschine);
+        } else if msg.path == "/maschine/idle_color" {
+            // no arguments goes back to a dimmed pad colour
+            self.idle_color = if msg.arguments.is_empty() {
+                None
+            } else {
+                match parse_hsl(&msg.arguments) {
+                    Some(color) => Some(color),
+                    None => return Err(OscError::BadArguments),
+                }
+            };
+
+            self.update_pad_colors(maschine);
+        } else if msg.path == "/maschine/theme" {
+            let name = match *msg.arguments {
+                [osc::Argument::s(name)] => name,
+                _ => return Err(OscError::BadArguments),
+            };
+
+            // themes from --theme can replace the built-in ones
+            let custom = self.config.themes.iter().find(|(n, _)| *n == name);
+            let palette = match custom {
+                Some(&(_, palette)) => palette,
+                None => match THEMES.iter().find(|&&(n, _, _)| n == name) {
+                    Some(&(_, from, to)) => theme_gradient(hsl(from), hsl(to)),
+                    None => {
+                        println!(" :: unknown theme \"{}\"", name);
+                        return Ok(());
+                    }
+                },
+            };
+
+            self.palette = Some(palette);
+            self.update_pad_colors(maschine);
+        } else if msg.path == "/maschine/led_fade" {
+            let fade = match *msg.arguments {
+                [osc::Argument::i(ms)] if ms >= 0 => Duration::from_millis(ms as u64),
+                _ => return Err(OscError::BadArguments),
+            };
+
+            maschine.set_led_fade(fade);
+        } else if msg.path == "/maschine/gamma" {
+            let gamma = match *msg.arguments {
+                [osc::Argument::f(gamma)] => gamma,
+                _ => return Err(OscError::BadArguments),
+            };
+
+            let clamped = gamma.clamp(MIN_LED_GAMMA, MAX_LED_GAMMA);
+            if clamped != gamma {
+                println!(
+                    " :: gamma {} is outside {:.1}-{:.1}, using {}",
+                    gamma, MIN_LED_GAMMA, MAX_LED_GAMMA, clamped
+                );
+            }
+
+            maschine.set_led_gamma(clamped);
+        } else if msg.path == "/maschine/channel" {
+            let channel = match *msg.arguments {
+                [osc::Argument::i(channel @ 1..=16)] => CHANNELS[channel as usize - 1],
+                _ => return Err(OscError::BadArguments),
+            };
+
+            self.set_pad_channel(maschine, channel);
+        } else if msg.path == "/maschine/velocity_scale" {
+            self.velocity_scale = match *msg.arguments {
+                [osc::Argument::f(scale)] if scale >= 0.0 => scale,
+                _ => return Err(OscError::BadArguments),
+            };
+        } else if msg.path == "/maschine/idle_brightness" {
+            if msg.arguments.len() != 1 {
+                return Err(OscError::BadArguments);
+            }
+
+            self.dark = false;
+            self.idle_brightness = match msg.arguments[0] {
+                osc::Argument::f(brightness) => brightness.clamp(0.0, 1.0),
+                _ => return Err(OscError::BadArguments),
+            };
+
+            self.update_pad_colors(maschine);
+        } else if msg.path == "/maschine/trigger" {
+            return self.handle_trigger_msg(maschine, msg);
+        } else if msg.path == "/maschine/screen/dump" {
+            self.send_screen_dump(maschine);
+        } else if msg.path == "/maschine/leds/get" {
+            self.send_light_frame(maschine);
+        } else if msg.path == "/maschine/leds/set" {
+            let frame = match parse_light_frame(&msg.arguments) {
+                Some(frame) => frame,
+                None => return Err(OscError::BadArguments),
+            };
+
+            // it all goes out with the next write of the lights, so the device never shows
+            // half of one frame and half of another
+            maschine.clear_lights();
+            for (pad, &(color, brightness)) in frame.pads.iter().enumerate() {
+                maschine.set_pad_light(pad, color, brightness);
+            }
+            for &(btn, color, brightness) in &frame.buttons {
+                maschine.set_button_light(btn, color, brightness);
+            }
+
+            self.pad_flashes = [None; 16];
+        } else if msg.path == "/maschine/encoder/label" {
+            match *msg.arguments {
+                [osc::Argument::s(label)] => {
+                    self.encoder_label = label.to_string();
+                    self.encoder_units.clear();
+                }
+                [osc::Argument::s(label), osc::Argument::s(units)] => {
+                    self.encoder_label = label.to_string();
+                    self.encoder_units = units.to_string();
+                }
+                _ => return Err(OscError::BadArguments),
+            }
+        } else if msg.path == "/maschine/sysex" {
+            let msg = match parse_sysex(&msg.arguments) {
+                Some(msg) => msg,
+                None => return Err(OscError::BadArguments),
+            };
+
+            if self.midi.send(&msg).is_err() {
+                println!(" :: couldn't send sysex");
+            }
+            self.midi.drain();
+        } else if msg.path == "/maschine/reset" {
+            self.reset(maschine);
+        } else if msg.path == "/maschine/reload" {
+            self.reload(maschine);
+        } else if msg.path == "/maschine/metronome" {
+            self.metronome = match *msg.arguments {
+                [osc::Argument::i(on)] => on != 0,
+                _ => return Err(OscError::BadArguments),
+            };
+
+            // it starts again from wherever the beat is now
+            self.metronome_beat = None;
+        } else if msg.path == "/maschine/mode" {
+            match *msg.arguments {
+                [osc::Argument::s(name), osc::Argument::i(on), ref params @ ..] => {
+                    self.set_feature_mode(maschine, name, on != 0, params)?
+                }
+                _ => return Err(OscError::BadArguments),
+            }
+        } else if msg.path == "/maschine/stats" {
+            self.send_osc_stats();
+        } else if msg.path == "/maschine/midi_stats" {
+            self.send_midi_stats();
+        } else if msg.path.starts_with("/maschine/midi_note_base") {
+            match *msg.arguments {
+                [osc::Argument::i(base @ 0..=127)] => self.set_note_base(maschine, base as u8),
+                _ => return Err(OscError::BadArguments),
+            }
+        } else {
+            return Err(OscError::UnknownPath);
+        }
+
+        Ok(())
+    }
+
+    fn send_osc_msg(&self, path: &str, arguments: Vec<osc::Argument>) {
+        let msg = osc::Message {
+            path: path,
+            arguments: arguments,
+        };
+
+        let repeats = self.config.osc_repeat(osc_kind(path));
+        udp::send_osc(self.osc_socket, &self.osc_outgoing_addrs, &msg, repeats);
+    }
+
+    fn tap_tempo(&mut self) {
+        let now = self.clock.now();
+
+        let timed_out = match self.tempo_taps.back() {
+            Some(last) => now.duration_since(*last).unwrap_or_default() > TEMPO_TAP_TIMEOUT,
+            None => false,
+        };
+
+        if timed_out {
+            self.tempo_taps.clear();
+        }
+
+        if self.tempo_taps.len() == TEMPO_TAPS {
+            self.tempo_taps.pop_front();
+        }
+
+        self.tempo_taps.push_back(now);
+        self.beat_origin = now;
+        self.metronome_beat = None;
+
+        let first = match self.tempo_taps.front() {
+            Some(first) if self.tempo_taps.len() > 1 => *first,
+            _ => return,
+        };
+
+        let span = now.duration_since(first).unwrap_or_default().as_secs_f32();
+        if span <= 0.0 {
+            return;
+        }
+
+        let interval = span / (self.tempo_taps.len() - 1) as f32;
+        self.tempo = 60.0 / interval;
+
+        println!(" :: tempo {:.1} bpm", self.tempo);
+        self.send_osc_msg("/maschine/tempo", vec![osc::Argument::f(self.tempo)]);
+    }
+
+    fn handle_trigger_msg(
+        &mut self,
+        maschine: &mut dyn Maschine,
+        msg: &osc::Message,
+    ) -> Result<(), OscError> {
+        if msg.arguments.len() < 2 || msg.arguments.len() > 3 {
+            return Err(OscError::BadArguments);
+        }
+
+        let pad_idx = match msg.arguments[0] {
+            osc::Argument::i(pad @ 0..=15) => pad as usize,
+            _ => return Err(OscError::BadArguments),
+        };
+
+        // floats are pad pressure, ints are a 0-127 velocity scaled to pressure, so both go
+        // through the pressure curve the same way a real hit does
+        let pressure = match msg.arguments[1] {
+            osc::Argument::f(pressure) => pressure.clamp(0.0, 1.0),
+            osc::Argument::i(velocity) => (velocity.clamp(0, 127) as f32) / 127.0,
+            _ => return Err(OscError::BadArguments),
+        };
+
+        let duration = match msg.arguments.get(2) {
+            Some(&osc::Argument::i(ms)) if ms > 0 => Some(Duration::from_millis(ms as u64)),
+            Some(_) => return Err(OscError::BadArguments),
+            None => None,
+        };
+
+        if pressure == 0.0 {
+            self.trigger_offs[pad_idx] = None;
+            self.pad_released(maschine, pad_idx);
+            return Ok(());
+        }
+
+        if self.held_notes[pad_idx].is_some() {
+            self.release_pad(maschine, pad_idx);
+        }
+
+        self.pad_pressed(maschine, pad_idx, pressure);
+        self.trigger_offs[pad_idx] = duration.map(|d| self.clock.now() + d);
+        Ok(())
+    }
+
+    fn send_osc_stats(&self) {
+        let stats = &self.osc_stats;
+
+        self.send_osc_msg(
+            "/maschine/stats",
+            osc_args![
+                stats.bytes as i32,
+                stats.received as i32,
+                stats.handled as i32,
+                stats.malformed as i32,
+                stats.unknown_path as i32,
+                stats.bad_arguments as i32
+            ],
+        );
+    }
+
+    fn send_light_frame(&self, maschine: &mut dyn Maschine) {
+        let lights = maschine.get_lights();
+        let mut arguments = Vec::with_capacity(32 + 3 * lights.buttons.len());
+
+        for &(color, brightness) in lights.pads.iter() {
+            arguments.push(osc::Argument::i(color as i32));
+            arguments.push(osc::Argument::f(brightness));
+        }
+
+        for &(btn, color, brightness) in lights.buttons.iter() {
+            arguments.push(osc::Argument::s(self.button_name(btn)));
+            arguments.push(osc::Argument::i(color as i32));
+            arguments.push(osc::Argument::f(brightness));
+        }
+
+        self.send_osc_msg("/maschine/leds", arguments);
+    }
+
+    fn send_screen_dump(&self, maschine: &mut dyn Maschine) {
+        let screen = maschine.get_screen();
+        let nchunks = screen.len().div_ceil(SCREEN_DUMP_CHUNK);
+
+        for (idx, chunk) in screen.chunks(SCREEN_DUMP_CHUNK).enumerate() {
+            let data = hex::encode(chunk);
+
+            self.send_osc_msg(
+                "/maschine/screen/dump",
+                osc_args![idx as i32, nchunks as i32, &*data],
+            );
+        }
+    }
+
+    fn send_osc_button_msg(
+        &mut self,
+        maschine: &mut dyn Maschine,
+        btn: MaschineButton,
+        status: usize,
+        pressed: bool,
+    ) {
+        // a toggle button's light is the toggle's and a mode button's the mode's, --led-follow
+        // leaves them alone
+        let mode_button = self.config.mode_buttons.iter().any(|&(b, _)| b == btn);
+
+        if self.config.toggle_buttons.contains(&btn) {
+            if pressed {
+                match self.toggled.iter().position(|&b| b == btn) {
+                    Some(i) => {
+                        self.toggled.remove(i);
+                    }
+                    None => self.toggled.push(btn),
+                }
+
+                self.update_toggle_light(maschine, btn);
+            }
+        } else if self.led_follow && !mode_button {
+            let brightness = if pressed { button_level(1.0) } else { 0.0 };
+            maschine.set_button_light(btn, hsl_to_rgb(self.base_color(maschine)), brightness);
+        }
+
+        let button = self.button_name(btn);
+
+        match button {
+            "tempo" if pressed => self.tap_tempo(),
+
+            "group_a" => self.select_group(maschine, 0),
+            "group_b" => self.select_group(maschine, 1),
+            "group_c" => self.select_group(maschine, 2),
+            "group_d" => self.select_group(maschine, 3),
+            "group_e" => self.select_group(maschine, 4),
+            "group_f" => self.select_group(maschine, 5),
+            "group_g" => self.select_group(maschine, 6),
+            "group_h" => self.select_group(maschine, 7),
+
+            // held pads keep the note they were hit with, so their note offs survive the shift
+            "page_left" if pressed && self.config.page_octaves => {
+                let base = maschine.get_midi_note_base().saturating_sub(12);
+                self.select_group_base(maschine, base);
+            }
+            "page_right" if pressed && self.config.page_octaves => {
+                let base = maschine.get_midi_note_base();
+                self.select_group_base(maschine, (base + 12).min(HIGHEST_NOTE_BASE).max(base));
+            }
+
+            _ => {}
+        }
+
+        match self.config.button_action(button) {
+            Some(ButtonAction::Note {
+                note,
+                channel,
+                velocity,
+            }) => {
+                // like a pad, a button only ends the note it started, and only once
+                let held = self
+                    .held_button_notes
+                    .iter()
+                    .position(|&(b, _, _)| b == btn);
+
+                match held {
+                    None if pressed => {
+                        self.held_button_notes.push((btn, channel, note));
+                        self.send_midi(&Message::NoteOn(channel, note, velocity));
+                    }
+                    Some(i) if !pressed => {
+                        let (_, channel, note) = self.held_button_notes.remove(i);
+                        self.send_midi(&Message::NoteOff(channel, note, 0));
+                    }
+                    _ => {}
+                }
+            }
+
+            None => {
+                if let Some((rpn, press_only)) = button_rpn(button) {
+                    if status > 0 || !press_only {
+                        let channel = self.config.button_channel(button);
+                        let msg = Message::RPN7(channel, rpn, status as u8);
+                        self.send_midi(&msg);
+                    }
+                }
+            }
+        }
+
+        // the modifiers held as the button went down or up, after it's been counted
+        self.send_osc_msg(
+            &*format!("/{}", button),
+            osc_args![status as f32, self.modifiers as i32],
+        );
+    }
+
+    fn modifier_held(&self, btn: MaschineButton) -> bool {
+        match self.config.modifiers.iter().position(|&m| m == btn) {
+            Some(bit) => self.modifiers & (1 << bit) != 0,
+            None => false,
+        }
+    }
+
+    // whether the button is one of the --modifiers and it's changed
+    fn set_modifier(&mut self, btn: MaschineButton, held: bool) -> bool {
+        let bit = match self.config.modifiers.iter().position(|&m| m == btn) {
+            Some(bit) => 1 << bit,
+            None => return false,
+        };
+
+        let was = self.modifiers;
+        if held {
+            self.modifiers |= bit;
+        } else {
+            self.modifiers &= !bit;
+        }
+
+        self.modifiers != was
+    }
+
+    fn modifier_changed(&mut self, maschine: &mut dyn Maschine, btn: MaschineButton) {
+        // the note grid shows the shift layer's notes while shift is held
+        if btn == MaschineButton::Shift && self.note_grid && self.shift_layer.is_some() {
+            self.draw_note_grid(maschine);
+        }
+    }
+
+    fn release_button_notes(&mut self) {
+        for (_, channel, note) in std::mem::take(&mut self.held_button_notes) {
+            self.send_midi(&Message::NoteOff(channel, note, 0));
+        }
+    }
+
+    // lets go of the sustain, ending what it held apart from notes a pad has played again
+    // since, which end with that pad
+    fn release_sustained_notes(&mut self) {
+        self.sustain_held = false;
+
+        for (channel, note) in std::mem::take(&mut self.sustained_notes) {
+            if ends_note(
+                &self.held_notes,
+                &self.pad_channels,
+                channel,
+                note,
+                SharedNotePolicy::Last,
+            ) {
+                self.send_note_off(Duration::ZERO, channel, note);
+            }
+        }
+    }
+
+    // the button's /maschine/button name, which also picks its RPN, channel and bindings.
+    // --erase-as-stop keeps erase going by the name and RPN it used to have
+    fn button_name(&self, btn: MaschineButton) -> &'static str {
+        match btn {
+            MaschineButton::Erase if self.config.erase_as_stop => "stop",
+            _ => btn_to_osc_button_map(btn),
+        }
+    }
+
+    fn learn_button(&self, btn: MaschineButton) {
+        let button = self.button_name(btn);
+
+        if let Some(ButtonAction::Note {
+            note,
+            channel,
+            velocity,
+        }) = self.config.button_action(button)
+        {
+            println!(
+                " :: learn: {:?} is /maschine/button/{}, sends note {} at velocity {} on channel {}",
+                btn,
+                button,
+                note,
+                velocity,
+                channel as u8 + 1
+            );
+            self.send_osc_msg("/maschine/learn", osc_args!["button", button, -1]);
+            return;
+        }
+
+        match button_rpn(button) {
+            Some((rpn, press_only)) => println!(
+                " :: learn: {:?} is /maschine/button/{}, sends RPN {}{} on channel {}",
+                btn,
+                button,
+                rpn,
+                if press_only { " on press" } else { "" },
+                self.config.button_channel(button) as u8 + 1
+            ),
+            None => println!(" :: learn: {:?} is /maschine/button/{}", btn, button),
+        }
+
+        let rpn = button_rpn(button).map_or(-1, |(rpn, _)| rpn as i32);
+        self.send_osc_msg("/maschine/learn", osc_args!["button", button, rpn]);
+    }
+
+    fn learn_pad(&self, pad_idx: usize, note: Option<U7>) {
+        match note {
+            Some(note) => println!(
+                " :: learn: pad {} sends note {} on channel {}",
+                pad_idx,
+                note,
+                self.pad_channels[pad_idx] as u8 + 1
+            ),
+            None => println!(" :: learn: pad {} is out of the MIDI note range", pad_idx),
+        }
+
+        let note = note.map_or(-1, |note| note as i32);
+        self.send_osc_msg("/maschine/learn", osc_args!["pad", pad_idx as i32, note]);
+    }
+
+    fn show_encoder_readout(&mut self, maschine: &mut dyn Maschine) {
+        let screen = maschine.get_screen_mut();
+
+        if self.readout_saved.is_none() {
+            self.readout_saved = Some(screen.to_vec());
+        }
+
+        let value = format!("{}{}", self.encoder_value, self.encoder_units);
+
+        screen::clear_rect(screen, 0, 0, SCREEN_WIDTH, SCREEN_HEIGHT);
+        screen::draw_text(screen, 4, 4, 2, &self.encoder_label);
+        screen::draw_text(screen, 4, 30, 4, &value);
+        maschine.send_screen();
+
+        self.readout_until = Some(self.clock.now() + ENCODER_READOUT_TIME);
+    }
+
+    fn dismiss_encoder_readout(&mut self, maschine: &mut dyn Maschine) {
+        self.readout_until = None;
+
+        if let Some(saved) = self.readout_saved.take() {
+            maschine.get_screen_mut().copy_from_slice(&saved);
+            maschine.send_screen();
+        }
+    }
+
+    fn send_osc_encoder_msg(&self, delta: i32) {
+        let steps = match self.config.encoder_format {
+            EncoderFormat::Int => osc::Argument::i(delta),
+            EncoderFormat::Float => osc::Argument::f(delta as f32),
+            EncoderFormat::Direction => osc::Argument::s(if delta > 0 { "+1" } else { "-1" }),
+            EncoderFormat::Absolute => {
+                return self.send_osc_msg("/maschine/encoder", osc_args![self.encoder_value]);
+            }
+        };
+
+        match self.config.encoder_mode {
+            EncoderMode::Relative => self.send_osc_msg("/maschine/encoder", vec![steps]),
+            EncoderMode::Wrap | EncoderMode::Clamp => self.send_osc_msg(
+                "/maschine/encoder",
+                vec![steps, osc::Argument::i(self.encoder_value)],
+            ),
+            EncoderMode::Screen
+            | EncoderMode::NoteBase
+            | EncoderMode::ListWrap
+            | EncoderMode::ListClamp => {}
+        }
+    }
+
+    // moves along --encoder-values and sends the value landed on. stopped at an end, turning
+    // further sends nothing
+    fn step_encoder_list(&mut self, maschine: &mut dyn Maschine, delta: i32) {
+        let values = &self.config.encoder_values;
+        let last = values.len() as i32 - 1;
+
+        let index = match self.config.encoder_mode {
+            EncoderMode::ListWrap => (self.encoder_index as i32 + delta).rem_euclid(last + 1),
+            _ => (self.encoder_index as i32 + delta).clamp(0, last),
+        } as usize;
+
+        if index == self.encoder_index && self.config.encoder_mode == EncoderMode::ListClamp {
+            return;
+        }
+
+        let value = values[index];
+        self.encoder_index = index;
+        self.encoder_value = value as i32;
+
+        if self.encoder_readout {
+            self.show_encoder_readout(maschine);
+        }
+
+        self.send_osc_msg(
+            "/maschine/encoder/value",
+            osc_args![index as i32, value as i32],
+        );
+
+        let channel = self.config.button_channel("encoder");
+        match self.config.encoder_midi {
+            Some(EncoderMidi::Program) => self.send_midi(&Message::ProgramChange(channel, value)),
+            Some(EncoderMidi::Cc(cc)) => {
+                self.send_midi(&Message::ControlChange(channel, cc, value))
+            }
+            None => {}
+        }
+    }
+
+    fn set_screen_brightness(&mut self, maschine: &mut dyn Maschine, brightness: f32) {
+        self.screen_brightness = brightness.clamp(0.0, 1.0);
+        maschine.set_screen_brightness(self.screen_brightness);
+    }
+
+    fn encoder_pushed(&mut self, maschine: &mut dyn Maschine, pressed: bool) {
+        // a push confirms whatever the encoder was turned to, so anything still held back for
+        // the tick has to go out ahead of it
+        if pressed && self.pending_encoder != 0 {
+            self.flush_coalesced(maschine);
+        }
+
+        self.encoder_held = pressed;
+        self.encoder_fine_steps = 0.0;
+
+        self.send_osc_msg("/maschine/encoder/push", osc_args![pressed as i32]);
+
+        if pressed {
+            self.centre_encoder(maschine);
+        }
+    }
+
+    // a push puts whatever the encoder is turning back in the middle of its range, sent on the
+    // same way a turn there would be. the note base and plain steps have no middle to go to
+    fn centre_encoder(&mut self, maschine: &mut dyn Maschine) {
+        match self.config.encoder_mode {
+            EncoderMode::Wrap | EncoderMode::Clamp => {
+                let delta = ENCODER_CENTRE - self.encoder_value;
+                if delta == 0 {
+                    return;
+                }
+
+                self.encoder_value = ENCODER_CENTRE;
+                if self.encoder_readout {
+                    self.show_encoder_readout(maschine);
+                }
+                self.send_osc_encoder_msg(delta);
+            }
+
+            EncoderMode::ListWrap | EncoderMode::ListClamp => {
+                let middle = self.config.encoder_values.len() / 2;
+                let delta = middle as i32 - self.encoder_index as i32;
+                if delta != 0 {
+                    self.step_encoder_list(maschine, delta);
+                }
+            }
+
+            EncoderMode::Screen => self.set_screen_brightness(maschine, 0.5),
+            EncoderMode::Relative | EncoderMode::NoteBase => {}
+        }
+    }
+
+    // the range above --aftertouch-threshold is stretched over the whole of 0-127
+    fn aftertouch_value(&self, pressure: f32) -> U7 {
+        let threshold = self.config.aftertouch_threshold;
+
+        if threshold >= 1.0 {
+            return 0;
+        }
+
+        let pressure = ((pressure - threshold) / (1.0 - threshold)).clamp(0.0, 1.0);
+        self.scale_velocity(self.pressure_to_vel(pressure))
+    }
+
+    // the pressure standing in for every pad held on pad_idx's channel, per --aftertouch-held.
+    // the device already has the pressures from this report for the other pads, unless they're
+    // being smoothed
+    fn held_pressure(&self, maschine: &dyn Maschine, pad_idx: usize, pressure: f32) -> f32 {
+        let channel = self.pad_channels[pad_idx];
+
+        let mut held = vec![];
+        for pad in 0..16 {
+            if self.held_notes[pad].is_none() || self.pad_channels[pad] != channel {
+                continue;
+            }
+
+            if pad == pad_idx {
+                held.push((pad, pressure));
+            } else if self.config.smoothing > 0.0 {
+                held.push((pad, self.smoothed_pressure[pad]));
+            } else {
+                held.push((pad, maschine.get_pad_pressure(pad).unwrap_or(0.0)));
+            }
+        }
+
+        if held.is_empty() {
+            return pressure;
+        }
+
+        let sum: f32 = held.iter().map(|&(_, pressure)| pressure).sum();
+
+        match (self.config.aftertouch, self.config.aftertouch_held) {
+            (Some(AftertouchMode::Cc), AftertouchHeld::Each) => pressure,
+
+            (_, AftertouchHeld::Each) | (_, AftertouchHeld::Max) => held
+                .iter()
+                .map(|&(_, pressure)| pressure)
+                .fold(0.0, f32::max),
+
+            (_, AftertouchHeld::Latest) => held
+                .iter()
+                .max_by_key(|&&(pad, _)| self.held_since[pad])
+                .map_or(pressure, |&(_, pressure)| pressure),
+
+            (_, AftertouchHeld::Average) => sum / held.len() as f32,
+            (_, AftertouchHeld::Sum) => sum.min(1.0),
+        }
+    }
+
+    fn send_aftertouch_msg(&self, maschine: &mut dyn Maschine, pad_idx: usize, pressure: f32) {
+        let midi_note = match self.held_notes[pad_idx] {
+            Some(note) => note,
+            None => return,
+        };
+
+        let channel = self.pad_channels[pad_idx];
+
+        let msg = match self.config.aftertouch {
+            Some(AftertouchMode::Cc) => {
+                let pressure = self.held_pressure(maschine, pad_idx, pressure);
+                Message::ControlChange(
+                    channel,
+                    self.config.aftertouch_cc,
+                    self.aftertouch_value(pressure),
+                )
+            }
+
+            Some(AftertouchMode::Channel) => {
+                let pressure = self.held_pressure(maschine, pad_idx, pressure);
+                Message::ChannelPressure(channel, self.aftertouch_value(pressure))
+            }
+
+            _ => Message::PolyphonicPressure(channel, midi_note, self.aftertouch_value(pressure)),
+        };
+
+        self.send_midi(&msg);
+    }
+
+    // the next point on the --quantize grid from now, or now itself without it
+    fn next_grid_point(&self, now: SystemTime) -> SystemTime {
+        let subdivision = match self.quantize {
+            Some(subdivision) => subdivision,
+            None => return now,
+        };
+
+        let step = 60.0 / self.tempo as f64 / subdivision.steps_per_beat() as f64;
+        let since = now.duration_since(self.beat_origin).unwrap_or_default();
+        let steps = (since.as_secs_f64() / step).ceil();
+
+        self.beat_origin + Duration::from_secs_f64(steps * step)
+    }
+
+    fn click_metronome(&mut self, now: SystemTime) {
+        let beat_length = 60.0 / self.tempo as f64;
+        let since = now.duration_since(self.beat_origin).unwrap_or_default();
+        let beat = (since.as_secs_f64() / beat_length) as u64;
+
+        if self.metronome_beat == Some(beat) {
+            return;
+        }
+        self.metronome_beat = Some(beat);
+
+        let (beat_note, downbeat_note) = self.config.metronome_notes;
+        let (note, velocity) = if beat.is_multiple_of(METRONOME_BEATS_PER_BAR) {
+            (downbeat_note, METRONOME_ACCENT_VELOCITY)
+        } else {
+            (beat_note, METRONOME_VELOCITY)
+        };
+
+        let channel = self.config.metronome_channel;
+        self.send_midi(&Message::NoteOn(channel, note, velocity));
+        self.send_after(METRONOME_CLICK_LENGTH, Message::NoteOff(channel, note, 0));
+    }
+
+    fn send_after(&mut self, delay: Duration, msg: Message) {
+        if delay == Duration::ZERO {
+            self.send_midi(&msg);
+        } else {
+            self.quantized.push((self.clock.now() + delay, msg));
+        }
+    }
+
+    // ends the pad's note and puts its light back, whatever mode the pads are in
+    fn release_pad(&mut self, maschine: &mut dyn Maschine, pad_idx: usize) {
+        // don't let a held-back pressure value trail behind the note off
+        self.pending_aftertouch[pad_idx] = None;
+
+        if self.config.osc_pressure {
+            self.pending_osc_pressure[pad_idx] = Some(0.0);
+        }
+        let held_since = self.held_since[pad_idx].take();
+        self.pad_peaks[pad_idx] = 0.0;
+
+        // a held back note keeps the length it was played with, and one let go of too soon
+        // is stretched out to --min-note
+        if let Some(midi_note) = self.held_notes[pad_idx].take() {
+            let channel = self.pad_channels[pad_idx];
+            let mut delay = self.quantize_delay[pad_idx];
+            if let (Some(min_note), Some(since)) = (self.config.min_note, held_since) {
+                let held_for = self.clock.now().duration_since(since).unwrap_or_default();
+                delay += min_note.saturating_sub(held_for);
+            }
+
+            if ends_note(
+                &self.held_notes,
+                &self.pad_channels,
+                channel,
+                midi_note,
+                self.config.shared_notes,
+            ) {
+                if !self.sustain_held {
+                    self.send_note_off(delay, channel, midi_note);
+                } else if !self.sustained_notes.contains(&(channel, midi_note)) {
+                    self.sustained_notes.push((channel, midi_note));
+                }
+            }
+        }
+
+        if self.dark {
+            self.dark = false;
+            self.idle_brightness = self.config.idle_brightness;
+            self.update_pad_colors(maschine);
+            return;
+        }
+
+        let color = self.idle_pad_color(maschine, pad_idx);
+        self.light_pad(maschine, pad_idx, color, self.idle_brightness);
+    }
+
+    // held buttons that are due fire again as though pressed, at most once a tick however far
+    // behind it's fallen
+    fn auto_repeat(&mut self, maschine: &mut dyn Maschine, now: SystemTime) {
+        for idx in 0..self.repeating.len() {
+            let (btn, byte, due) = self.repeating[idx];
+            if now < due {
+                continue;
+            }
+
+            let interval = match self.config.auto_repeat(btn) {
+                Some(repeat) => repeat.interval,
+                None => continue,
+            };
+            self.repeating[idx].2 = if due + interval > now {
+                due + interval
+            } else {
+                now + interval
+            };
+
+            self.send_osc_button_msg(maschine, btn, byte as usize, true);
+        }
+    }
+
+    // makes room for the pad's note under --max-poly by ending the oldest. pads whose notes
+    // have ended some other way since, by being released or choked, drop out of the order here
+    fn steal_voice(&mut self, maschine: &mut dyn Maschine, pad_idx: usize) {
+        let held_notes = &self.held_notes;
+        self.voices
+            .retain(|&pad| pad != pad_idx && held_notes[pad].is_some());
+
+        if let Some(max_poly) = self.config.max_poly {
+            while self.voices.len() >= max_poly {
+                let oldest = self.voices.pop_front().unwrap();
+                self.one_shot_offs[oldest] = None;
+                self.release_pad(maschine, oldest);
+            }
+        }
+
+        self.voices.push_back(pad_idx);
+    }
+
+    // the last hit's --min-note off mustn't land after the next note on for the same note
+    // and cut it short, so it goes out just before it instead
+    fn bring_forward_note_off(&mut self, channel: Channel, note: U7, delay: Duration) {
+        let is_off =
+            |msg: &Message| matches!(*msg, Message::NoteOff(c, n, _) if c == channel && n == note);
+
+        // one going out now goes out now too. a held back one keeps its place in the queue,
+        // ahead of the note on pushed after it
+        if delay == Duration::ZERO {
+            let (due, rest) = self.quantized.drain(..).partition(|(_, msg)| is_off(msg));
+            self.quantized = rest;
+            for (_, msg) in due {
+                self.send_midi(&msg);
+            }
+        } else {
+            let on_at = self.clock.now() + delay;
+            for (at, msg) in &mut self.quantized {
+                if is_off(msg) && *at > on_at {
+                    *at = on_at;
+                }
+            }
+        }
+    }
+
+    // a note off for a note whose note on --roll-limit is still holding back waits behind it
+    fn send_note_off(&mut self, delay: Duration, channel: Channel, note: U7) {
+        self.send_osc_note(channel, note, 0, false);
+
+        let msg = Message::NoteOff(channel, note, 0);
+        let rolled = self
+            .rolled
+            .iter()
+            .any(|held| matches!(*held, Message::NoteOn(c, n, _) if c == channel && n == note));
+
+        if rolled {
+            self.rolled.push_back(msg);
+        } else {
+            self.send_after(delay, msg);
+        }
+    }
+
+    // lets through as many held back hits as --roll-limit allows this tick, in the order they
+    // came in. the note offs behind them don't count
+    fn send_rolled(&mut self) {
+        let limit = self.config.roll_limit.unwrap_or(usize::MAX);
+
+        while let Some(msg) = self.rolled.front() {
+            if let Message::NoteOn(..) = *msg {
+                if self.note_ons_this_tick >= limit {
+                    break;
+                }
+                self.note_ons_this_tick += 1;
+            }
+
+            let msg = self.rolled.pop_front().unwrap();
+            self.send_midi(&msg);
+        }
+    }
+
+    // sends held back messages that are due by until, or all of them, in the order they're due
+    fn send_quantized(&mut self, until: Option<SystemTime>) {
+        self.quantized.sort_by_key(|&(at, _)| at);
+
+        let due = match until {
+            Some(until) => self
+                .quantized
+                .iter()
+                .take_while(|&&(at, _)| at <= until)
+                .count(),
+            None => self.quantized.len(),
+        };
+
+        for (_, msg) in self.quantized.drain(..due).collect::<Vec<_>>() {
+            self.send_midi(&msg);
+        }
+    }
+
+    fn flush_coalesced(&mut self, maschine: &mut dyn Maschine) {
+        if self.pending_encoder != 0 {
+            self.send_osc_encoder_msg(self.pending_encoder);
+            self.pending_encoder = 0;
+        }
+
+        // held back aftertouch is as late as the report it came from
+        let input_at = self.input_at;
+
+        for pad_idx in 0..16 {
+            if let Some((pressure, at)) = self.pending_aftertouch[pad_idx].take() {
+                self.input_at = at;
+                self.send_aftertouch_msg(maschine, pad_idx, pressure);
+            }
+        }
+
+        self.input_at = input_at;
+    }
+
+    fn send_midi(&self, msg: &Message) {
+        // a batch can fill the sequencer's output buffer, which a drain makes room in again
+        if self.midi.send(msg).is_err() {
+            self.drain_midi();
+
+            if self.midi.send(msg).is_err() {
+                let mut stats = self.midi_stats.get();
+                stats.dropped += 1;
+                self.midi_stats.set(stats);
+                return;
+            }
+        }
+
+        let mut stats = self.midi_stats.get();
+        stats.messages += 1;
+        self.midi_stats.set(stats);
+
+        self.midi_unsent.set(true);
+        if !self.config.midi_batch {
+            self.drain_midi();
+        }
+
+        if !self.config.latency {
+            return;
+        }
+
+        if let Some(at) = self.input_at {
+            let latency = self.clock.since(at);
+            let mut stats = self.latency.get();
+
+            stats.min = match stats.count {
+                0 => latency,
+                _ => stats.min.min(latency),
+            };
+            stats.max = stats.max.max(latency);
+            stats.total += latency;
+            stats.count += 1;
+
+            self.latency.set(stats);
+        }
+    }
+
+    fn drain_midi(&self) {
+        if !self.midi_unsent.replace(false) {
+            return;
+        }
+
+        self.midi.drain();
+
+        let mut stats = self.midi_stats.get();
+        stats.drains += 1;
+        self.midi_stats.set(stats);
+    }
+
+    // with --osc-notes, the pads' notes also go out as /maschine/note, channels counted from 1.
+    // they go as the pad is hit or let go of, even if --quantize or --roll-limit hold back the
+    // MIDI
+    fn send_osc_note(&self, channel: Channel, note: U7, velocity: U7, on: bool) {
+        if !self.config.osc_notes {
+            return;
+        }
+
+        self.send_osc_msg(
+            "/maschine/note",
+            osc_args![channel as i32 + 1, note as i32, velocity as i32, on as i32],
+        );
+    }
+
+    fn send_midi_stats(&self) {
+        let stats = self.midi_stats.get();
+
+        self.send_osc_msg(
+            "/maschine/midi_stats",
+            osc_args![
+                stats.messages as i32,
+                stats.drains as i32,
+                stats.dropped as i32
+            ],
+        );
+    }
+
+    fn report_latency(&mut self) {
+        let stats = self.latency.take();
+        self.latency_since = self.clock.now();
+
+        if stats.count == 0 {
+            return;
+        }
+
+        let ms = |d: Duration| d.as_secs_f32() * 1000.0;
+        let (min, avg, max) = (ms(stats.min), ms(stats.total / stats.count), ms(stats.max));
+
+        println!(
+            " :: latency: min {:.2}ms, avg {:.2}ms, max {:.2}ms over {} messages",
+            min, avg, max, stats.count
+        );
+        self.send_osc_msg(
+            "/maschine/latency",
+            osc_args![min, avg, max, stats.count as i32],
+        );
+    }
+}
+
+const PAD_NOTE_MAP: [U7; 16] = [12, 13, 14, 15, 8, 9, 10, 11, 4, 5, 6, 7, 0, 1, 2, 3];
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+// a quarter of the screen across and down for each pad, big enough for "C#-1" at double size
+const NOTE_GRID_CELL_WIDTH: usize = SCREEN_WIDTH / 4;
+const NOTE_GRID_CELL_HEIGHT: usize = SCREEN_HEIGHT / 4;
+
+// middle C, note 60, is C4
+fn note_name(note: U7) -> String {
+    let octave = (note / 12) as i32 - 1;
+    format!("{}{}", NOTE_NAMES[(note % 12) as usize], octave)
+}
+
+// --pad-notes gm, the General MIDI drum kit laid out from the top left: kick, snare and hi-hats
+// along the bottom, toms above them, the high toms with crash and ride above those, and
+// percussion along the top
+const GM_DRUM_PAD_NOTES: [U7; 16] = [
+    37, 39, 54, 56, 48, 50, 49, 51, 41, 43, 45, 47, 36, 38, 42, 46,
+];
+
+// the note a hit starts, if it starts one. a touch too soft to make a velocity would go out as
+// a note on of 0, which synths take for a note off, so it plays nothing and isn't held, and
+// its release has nothing to end
+fn hit_note(note: Option<U7>, velocity: U7) -> Option<U7> {
+    match velocity {
+        0 => None,
+        _ => note,
+    }
+}
+
+// whether a pad that's stopped holding the note should send its note off, going by the pads
+// still holding notes
+fn ends_note(
+    held_notes: &[Option<U7>; 16],
+    pad_channels: &[Channel; 16],
+    channel: Channel,
+    note: U7,
+    policy: SharedNotePolicy,
+) -> bool {
+    match policy {
+        SharedNotePolicy::Each => true,
+        SharedNotePolicy::Last => !held_notes
+            .iter()
+            .zip(pad_channels.iter())
+            .any(|(&held, &c)| held == Some(note) && c == channel),
+    }
+}
+
+fn pad_note(base: u8, pad_idx: usize, policy: NoteRangePolicy) -> Option<U7> {
+    let note = (base as u32) + (PAD_NOTE_MAP[pad_idx] as u32);
+
+    match note {
+        0..=127 => Some(note as U7),
+        _ => match policy {
+            NoteRangePolicy::Clamp => Some(127),
+            NoteRangePolicy::Drop => None,
+        },
+    }
+}
+
+impl<'a> MaschineHandler for MHandler<'a> {
+    fn get_fds(&self) -> Vec<RawFd> {
+        let mut fds = vec![self.osc_socket.as_raw_fd()];
+
+        if let Some(fd) = self.midi_in_fd {
+            fds.push(fd);
+        }
+
+        fds
+    }
+
+    fn report_arrived(&mut self, at: SystemTime) {
+        self.input_at = Some(at);
+    }
+
+    fn read_input(&mut self, maschine: &mut dyn Maschine, fd: RawFd) {
+        self.input_at = None;
+
+        if Some(fd) == self.midi_in_fd {
+            self.recv_midi_msg();
+        } else {
+            self.recv_osc_msg(maschine);
+        }
+    }
+
+    fn flush(&mut self) {
+        self.drain_midi();
+    }
+
+    fn disconnected(&mut self) {
+        self.all_notes_off();
+        self.drain_midi();
+    }
+
+    fn tick(&mut self, maschine: &mut dyn Maschine) {
+        self.input_at = None;
+
+        if self.config.latency && self.clock.since(self.latency_since) >= LATENCY_WINDOW {
+            self.report_latency();
+        }
+
+        if self.coalesce {
+            self.flush_coalesced(maschine);
+        }
+
+        let now = self.clock.now();
+
+        self.note_ons_this_tick = 0;
+        if !self.rolled.is_empty() {
+            self.send_rolled();
+        }
+
+        for ev in self.debounce.settle(now) {
+            ev.dispatch(maschine, self);
+        }
+
+        if self.metronome {
+            self.click_metronome(now);
+        }
+
+        for pad_idx in 0..16 {
+            if let Some(pressure) = self.pending_osc_pressure[pad_idx].take() {
+                let path = format!("/maschine/pad/{}/pressure", pad_idx);
+                self.send_osc_msg(&path, osc_args![pressure]);
+            }
+        }
+
+        if !self.quantized.is_empty() {
+            self.send_quantized(Some(now));
+        }
+
+        if !self.repeating.is_empty() {
+            self.auto_repeat(maschine, now);
+        }
+
+        if let Some(since) = self.light_test_since {
+            self.light_test(maschine, since);
+        }
+
+        for pad_idx in 0..16 {
+            match self.trigger_offs[pad_idx] {
+                Some(due) if now >= due => {
+                    self.trigger_offs[pad_idx] = None;
+                    self.pad_released(maschine, pad_idx);
+                }
+                _ => {}
+            }
+
+            match self.one_shot_offs[pad_idx] {
+                Some(due) if now >= due => {
+                    self.one_shot_offs[pad_idx] = None;
+                    self.release_pad(maschine, pad_idx);
+                }
+                _ => {}
+            }
+        }
+
+        if self
+            .pad_flashes
+            .iter()
+            .any(|flash| flash.is_some_and(|until| now >= until))
+        {
+            for flash in self.pad_flashes.iter_mut() {
+                if flash.is_some_and(|until| now >= until) {
+                    *flash = None;
+                }
+            }
+
+            self.update_pad_colors(maschine);
+        }
+
+        match self.readout_until {
+            Some(until) if now >= until => self.dismiss_encoder_readout(maschine),
+            _ => {}
+        }
+
+        if let Some(max_hold) = self.max_hold {
+            for pad_idx in 0..16 {
+                // a one-shot ends on its own
+                if self.one_shot_offs[pad_idx].is_some() {
+                    continue;
+                }
+
+                match self.held_since[pad_idx] {
+                    Some(since) if now.duration_since(since).unwrap_or_default() > max_hold => {
+                        println!(
+                            " :: pad {} held for over {}ms, releasing it",
+                            pad_idx,
+                            max_hold.as_millis()
+                        );
+                        self.release_pad(maschine, pad_idx);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn pad_pressed(&mut self, maschine: &mut dyn Maschine, pad_idx: usize, pressure: f32) {
+        let ev = MaschineEvent::PadPressed(pad_idx, pressure);
+        if !self.release_lockout.accept(ev, self.clock.now()) {
+            return;
+        }
+
+        // hitting a pad again before its one-shot is over starts it afresh
+        if self.one_shot_offs[pad_idx].take().is_some() {
+            self.release_pad(maschine, pad_idx);
+        }
+
+        // a latched note ends on the pad's next hit, which plays nothing itself
+        if self.mode == DriverMode::Latch && self.held_notes[pad_idx].is_some() {
+            self.release_pad(maschine, pad_idx);
+            return;
+        }
+
+        // the zone goes by how hard the pad was hit, before any scaling for the synth
+        let hit = self.pressure_to_vel(pressure);
+        let velocity = self.scale_velocity(hit);
+        let velocity = self.humanize.velocity(velocity);
+
+        // the note is kept in held_notes, so letting go of shift before the pad still ends it
+        let note = match self.config.zone_note(pad_idx, hit) {
+            Some(note) => Some(note),
+            None => self.current_pad_note(maschine, pad_idx),
+        };
+
+        if self.config.osc_pressure {
+            self.pending_osc_pressure[pad_idx] = Some(pressure);
+        }
+
+        // a new hit starts its own peak, and smoothing starts from it so it isn't held back
+        self.pad_peaks[pad_idx] = 0.0;
+        self.smoothed_pressure[pad_idx] = pressure;
+
+        if self.learn {
+            self.learn_pad(pad_idx, note);
+            let brightness = self.held_pad_brightness(pad_idx, pressure);
+            let color = self.pad_color(maschine, pad_idx);
+            self.light_pad(maschine, pad_idx, color, brightness);
+            return;
+        }
+
+        let note = hit_note(note, velocity);
+
+        let now = self.clock.now();
+        self.quantize_delay[pad_idx] = self
+            .next_grid_point(now)
+            .duration_since(now)
+            .unwrap_or_default();
+
+        // past the --roll-limit for this tick, or behind hits that already are
+        let over_limit = match self.config.roll_limit {
+            Some(limit) => self.note_ons_this_tick >= limit || !self.rolled.is_empty(),
+            None => false,
+        };
+        let rolled = note.is_some() && over_limit;
+
+        if rolled && self.config.roll_overflow == RollOverflow::Drop {
+            self.held_notes[pad_idx] = None;
+            self.held_since[pad_idx] = None;
+
+            let brightness = self.held_pad_brightness(pad_idx, pressure);
+            let color = self.pad_color(maschine, pad_idx);
+            self.light_pad(maschine, pad_idx, color, brightness);
+            return;
+        }
+
+        if let Some(group) = self.choke_groups[pad_idx] {
+            self.choke(group as usize, pad_idx, note);
+        }
+
+        if note.is_some() {
+            self.steal_voice(maschine, pad_idx);
+        }
+
+        self.held_notes[pad_idx] = note;
+        self.held_since[pad_idx] = note.map(|_| now);
+
+        if let Some(midi_note) = note {
+            let channel = self.pad_channels[pad_idx];
+            let msg = Message::NoteOn(channel, midi_note, velocity);
+            self.send_osc_note(channel, midi_note, velocity, true);
+
+            self.bring_forward_note_off(channel, midi_note, self.quantize_delay[pad_idx]);
+
+            if rolled {
+                self.rolled.push_back(msg);
+            } else {
+                self.note_ons_this_tick += 1;
+                self.send_after(self.quantize_delay[pad_idx], msg);
+            }
+
+            if self.mode == DriverMode::OneShot {
+                let length = self.one_shot_length;
+                self.one_shot_offs[pad_idx] = Some(now + self.quantize_delay[pad_idx] + length);
+            }
+        }
+
+        let brightness = self.held_pad_brightness(pad_idx, pressure);
+        let color = self.pad_color(maschine, pad_idx);
+        self.light_pad(maschine, pad_idx, color, brightness);
+    }
+
+    fn pad_aftertouch(&mut self, maschine: &mut dyn Maschine, pad_idx: usize, pressure: f32) {
+        let ev = MaschineEvent::PadAftertouch(pad_idx, pressure);
+        if !self.release_lockout.accept(ev, self.clock.now()) {
+            return;
+        }
+
+        if self.config.osc_pressure {
+            self.pending_osc_pressure[pad_idx] = Some(pressure);
+        }
+
+        let smoothing = self.config.smoothing;
+        let pressure = self.smoothed_pressure[pad_idx] * smoothing + pressure * (1.0 - smoothing);
+        self.smoothed_pressure[pad_idx] = pressure;
+
+        match self.pressure_shape {
+            PressureShape::Constant(_) => return,
+            _ => {}
+        }
+
+        if !self.send_aftertouch[pad_idx] {
+            return;
+        }
+
+        // no pressure for a note that hasn't started yet
+        let starts = self.held_since[pad_idx].map(|since| since + self.quantize_delay[pad_idx]);
+        if starts.is_some_and(|starts| starts > self.clock.now()) {
+            return;
+        }
+
+        // resting weight on a pad isn't expression
+        if pressure >= self.config.aftertouch_threshold {
+            if self.coalesce {
+                self.pending_aftertouch[pad_idx] = Some((pressure, self.input_at));
+            } else {
+                self.send_aftertouch_msg(maschine, pad_idx, pressure);
+            }
+        }
+
+        // a flash still running keeps the pad until it's over
+        let brightness = self.held_pad_brightness(pad_idx, pressure);
+        let color = self.pad_color(maschine, pad_idx);
+        self.light_pad(maschine, pad_idx, color, brightness);
+    }
+
+    fn pad_released(&mut self, maschine: &mut dyn Maschine, pad_idx: usize) {
+        let ev = MaschineEvent::PadReleased(pad_idx);
+        if !self.release_lockout.accept(ev, self.clock.now()) {
+            return;
+        }
+
+        match self.mode {
+            // the note ends when its one-shot is over, not when the pad is let go of
+            DriverMode::OneShot if self.one_shot_offs[pad_idx].is_some() => return,
+
+            // or on the pad's next hit
+            DriverMode::Latch if self.held_notes[pad_idx].is_some() => return,
+
+            _ => {}
+        }
+
+        self.release_pad(maschine, pad_idx);
+    }
+
+    fn encoder_step(&mut self, maschine: &mut dyn Maschine, _: usize, delta: i32) {
+        let delta = if self.encoder_held && self.config.encoder_fine < 1.0 {
+            self.encoder_fine_steps += delta as f32 * self.config.encoder_fine;
+
+            let whole = self.encoder_fine_steps.trunc();
+            self.encoder_fine_steps -= whole;
+            whole as i32
+        } else {
+            delta
+        };
+
+        if delta == 0 {
+            return;
+        }
+
+        if self.config.encoder_mode == EncoderMode::Screen {
+            let brightness = self.screen_brightness + delta as f32 * SCREEN_BRIGHTNESS_STEP;
+            self.set_screen_brightness(maschine, brightness);
+            return;
+        }
+
+        // held pads keep the notes they went on with, so nothing sticks as the base moves
+        if self.config.encoder_mode == EncoderMode::NoteBase {
+            let base = (maschine.get_midi_note_base() as i32 + delta).clamp(0, 127);
+            self.select_group_base(maschine, base as u8);
+            return;
+        }
+
+        if let EncoderMode::ListWrap | EncoderMode::ListClamp = self.config.encoder_mode {
+            self.step_encoder_list(maschine, delta);
+            return;
+        }
+
+        self.encoder_value = match self.config.encoder_mode {
+            EncoderMode::Wrap => (self.encoder_value + delta).rem_euclid(128),
+            EncoderMode::Relative
+            | EncoderMode::Clamp
+            | EncoderMode::Screen
+            | EncoderMode::NoteBase
+            | EncoderMode::ListWrap
+            | EncoderMode::ListClamp => (self.encoder_value + delta).clamp(0, 127),
+        };
+
+        if self.encoder_readout {
+            self.show_encoder_readout(maschine);
+        }
+
+        if self.coalesce {
+            self.pending_encoder += delta;
+        } else {
+            self.send_osc_encoder_msg(delta);
+        }
+    }
+
+    fn button_down(&mut self, maschine: &mut dyn Maschine, btn: MaschineButton, byte: u8) {
+        if !self.debounce.accept(btn, true, byte, self.clock.now()) {
+            return;
+        }
+
+        if self.set_modifier(btn, true) {
+            self.modifier_changed(maschine, btn);
+        }
+
+        if self.config.sustain_button == Some(btn) {
+            self.sustain_held = true;
+        }
+
+        if self.learn {
+            self.learn_button(btn);
+            return;
+        }
+
+        if let Some(&(_, mode)) = self.config.mode_buttons.iter().find(|&&(b, _)| b == btn) {
+            self.set_mode(maschine, mode);
+        }
+
+        if let Some(repeat) = self.config.auto_repeat(btn) {
+            let first = self.clock.now() + repeat.delay;
+            self.repeating.retain(|&(b, _, _)| b != btn);
+            self.repeating.push((btn, byte, first));
+        }
+
+        // the Mikro reports pushing the encoder down as the nav button
+        if let MaschineButton::Nav = btn {
+            self.encoder_pushed(maschine, true);
+        }
+
+        self.send_osc_button_msg(maschine, btn, byte as usize, true);
+    }
+
+    fn button_up(&mut self, maschine: &mut dyn Maschine, btn: MaschineButton, byte: u8) {
+        if !self.debounce.accept(btn, false, byte, self.clock.now()) {
+            return;
+        }
+
+        if self.set_modifier(btn, false) {
+            self.modifier_changed(maschine, btn);
+        }
+
+        if self.config.sustain_button == Some(btn) {
+            self.release_sustained_notes();
+        }
+
+        self.repeating.retain(|&(b, _, _)| b != btn);
+
+        if self.learn {
+            return;
+        }
+
+        if let MaschineButton::Nav = btn {
+            self.encoder_pushed(maschine, false);
+        }
+
+        self.send_osc_button_msg(maschine, btn, byte as usize, false);
+    }
+}
+
+// the name the kernel gives the HID device behind a /dev/hidrawN node
+fn hid_name(dev_path: &str) -> Option<String> {
+    let node = Path::new(dev_path).file_name()?.to_str()?;
+    let uevent = fs::read_to_string(format!("/sys/class/hidraw/{}/device/uevent", node)).ok()?;
+
+    uevent
+        .lines()
+        .find_map(|line| line.strip_prefix("HID_NAME="))
+        .map(|name| name.to_string())
+}
+
+fn print_version(config: &Config) {
+    println!("maschine.rs {}", env!("CARGO_PKG_VERSION"));
+    println!("  devices:  {}", DEVICES.join(", "));
+
+    if let Some(ref path) = config.device {
+        match hid_name(path) {
+            Some(name) => println!("  device:   {} ({})", path, name),
+            None => println!("  device:   {} (unknown)", path),
+        }
+    }
+
+    let destinations: Vec<_> = config
+        .osc_destinations()
+        .iter()
+        .map(|addr| addr.to_string())
+        .collect();
+    println!(
+        "  OSC:      listening on {}, sending to {}",
+        config.osc_listen,
+        destinations.join(", ")
+    );
+    if config.led_palette {
+        let colours = devices::mk2::LED_PALETTE.len();
+        println!("  pad LEDs: palette of {} colours", colours);
+    } else {
+        println!("  pad LEDs: RGB, 7 bits a channel");
+    }
+    println!(
+        "  MIDI:     ALSA client \"{}\", ports \"{}\" (out) and \"{}\" (in)",
+        config.midi_name, config.midi_out_port, config.midi_in_port
+    );
+}
+
+fn main() {
+    let args: Vec<_> = env::args().collect();
+
+    let vars = config::env_vars();
+
+    let config = match Config::from_env_and_args(&vars, &args[1..]) {
+        Ok(config) => config,
+        Err(err) => {
+            println!(" :: {}", err);
+            usage(&args[0]);
+            process::exit(1);
+        }
+    };
+
+    if config.show_version {
+        print_version(&config);
+        return;
+    }
+
+    let problems = config.validate();
+    for problem in &problems {
+        match *problem {
+            Problem::Error(ref err) => println!(" :: config error: {}", err),
+            Problem::Warning(ref warning) => println!(" :: config warning: {}", warning),
+        }
+    }
+
+    let errors = problems
+        .iter()
+        .filter(|problem| matches!(problem, Problem::Error(_)))
+        .count();
+    if errors > 0 {
+        println!(" :: {} config errors, not starting", errors);
+        process::exit(1);
+    }
+
+    let pressure_shape = match load_pressure_shape(&config) {
+        Ok(shape) => shape,
+        Err(err) => {
+            println!(" :: {}", err);
+            process::exit(1);
+        }
+    };
+
+    // the self-test talks to itself, so it mustn't take the port from a running driver
+    let osc_listen_addr = if config.self_test {
+        SocketAddr::from(([127, 0, 0, 1], 0))
+    } else {
+        config.osc_listen
+    };
+    let osc_socket = UdpSocket::bind(osc_listen_addr).unwrap();
+
+    let sequencer = match AlsaSequencer::open(&config.midi_name) {
+        Ok(sequencer) => sequencer,
+        Err(err) => {
+            println!(" :: {}", err);
+            process::exit(1);
+        }
+    };
+    let midi = match sequencer.create_ports(&config.midi_out_port, &config.midi_in_port) {
+        Ok(midi) => midi,
+        Err(err) => {
+            println!(" :: {}", err);
+            process::exit(1);
+        }
+    };
+
+    let clock: Rc<dyn Clock> = Rc::new(SystemClock);
+
+    let config = Rc::new(config);
+
+    let mut handler = MHandler::new(
+        config.clone(),
+        vars,
+        args[1..].to_vec(),
+        pressure_shape,
+        clock.clone(),
+        &midi,
+        &osc_socket,
+    );
+
+    if config.self_test {
+        process::exit(if selftest::run(&mut handler) { 0 } else { 1 });
+    }
+
+    let mut dev: Box<dyn Maschine> = match config.input {
+        InputSource::Hid => {
+            let dev_path = match config.device {
+                Some(ref path) => path.clone(),
+                None => {
+                    usage(&args[0]);
+                    panic!("missing hidraw device path");
+                }
+            };
+
+            let dev_fd = match fcntl::open(
+                Path::new(&dev_path),
+                O_RDWR | O_NONBLOCK,
+                sys::stat::Mode::empty(),
+            ) {
+                Err(err) => panic!("couldn't open {}: {}", dev_path, err.errno().desc()),
+                Ok(file) => file,
+            };
+
+            let mut mikro = devices::mk2::Mikro::new(dev_fd);
+            mikro.set_clock(clock.clone());
+            if !config.screen {
+                mikro.disable_screen();
+            }
+
+            if config.led_palette {
+                mikro.set_led_colors(devices::mk2::LedColors::Palette);
+            }
+
+            Box::new(mikro)
+        }
+
+        InputSource::Keyboard => match devices::keyboard::Keyboard::new() {
+            Ok(keyboard) => Box::new(keyboard),
+            Err(err) => panic!("couldn't use the keyboard: {}", err),
+        },
+    };
+
+    dev.set_led_fade(config.led_fade);
+    dev.set_led_gamma(config.led_gamma);
+
+    let run_config = RunConfig {
+        light_interval: config.light_interval,
+        light_refresh: config.light_refresh,
+
+        watchdog: config
+            .watchdog
+            .map(|threshold| maschine::spawn_watchdog(threshold, config.watchdog_exit)),
+
+        clock,
+
+        ..RunConfig::default()
+    };
+
+    handler.init_device(&mut *dev);
+
+    if config.light_test {
+        handler.light_test_since = Some(handler.clock.now());
     }
 
-    ev_loop(&mut dev, &mut handler);
+    maschine::run(&mut *dev, &mut handler, &run_config);
 }
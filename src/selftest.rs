@@ -0,0 +1,139 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+use std::net::UdpSocket;
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+use base::{Maschine, MaschineButton, MaschineHandler};
+use devices::mock::MockMaschine;
+
+use {osc, MHandler};
+
+// how long to wait for a message to come back round before calling it lost
+const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+type Check = fn(&MockMaschine, &MHandler) -> bool;
+
+fn all_pads_follow(dev: &MockMaschine, handler: &MHandler) -> bool {
+    (0..16).all(|i| dev.pad_lights[i].0 == handler.pad_color(dev, i))
+}
+
+// sends the handler's own OSC socket a message for each kind of thing it handles, the same way
+// a client would, and checks each one landed on the device or in the handler
+pub fn run(handler: &mut MHandler) -> bool {
+    let mut dev = MockMaschine::default();
+    handler.init_device(&mut dev);
+
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let listen_addr = handler.osc_socket.local_addr().unwrap();
+    let listen_fd = handler.osc_socket.as_raw_fd();
+
+    handler
+        .osc_socket
+        .set_read_timeout(Some(SELF_TEST_TIMEOUT))
+        .unwrap();
+
+    let steps: Vec<(&str, osc::Message, Check)> = vec![
+        (
+            "button brightness",
+            osc::Message {
+                path: "/maschine/button/play",
+                arguments: osc_args![0.5f32],
+            },
+            |dev, _| dev.button_light(MaschineButton::Play) == Some((0xFFFFFF, 0.5)),
+        ),
+        (
+            "button colour",
+            osc::Message {
+                path: "/maschine/button/rec",
+                arguments: osc_args![0xFF0000, 1.0f32],
+            },
+            |dev, _| dev.button_light(MaschineButton::Rec) == Some((0xFF0000, 1.0)),
+        ),
+        (
+            "pad colour",
+            osc::Message {
+                path: "/maschine/pad",
+                arguments: osc_args![3, 0, 255, 0, 0.75f32],
+            },
+            |dev, _| dev.pad_lights[3] == (0x00FF00, 0.75),
+        ),
+        (
+            "note base",
+            osc::Message {
+                path: "/maschine/midi_note_base",
+                arguments: osc_args![60],
+            },
+            |dev, _| dev.get_midi_note_base() == 60,
+        ),
+        (
+            "colour",
+            osc::Message {
+                path: "/maschine/color",
+                arguments: osc_args![0x0000FF],
+            },
+            |dev, handler| handler.color.is_some() && all_pads_follow(dev, handler),
+        ),
+        (
+            "group colours",
+            osc::Message {
+                path: "/maschine/color",
+                arguments: osc_args![],
+            },
+            |dev, handler| handler.color.is_none() && all_pads_follow(dev, handler),
+        ),
+        (
+            "unknown path",
+            osc::Message {
+                path: "/maschine/self_test/nothing",
+                arguments: osc_args![],
+            },
+            |_, handler| handler.osc_stats.unknown_path == 1,
+        ),
+    ];
+
+    let mut failed = 0;
+
+    for (name, msg, check) in steps {
+        let received = handler.osc_stats.received;
+
+        sender
+            .send_to(&msg.serialize().unwrap(), listen_addr)
+            .unwrap();
+        handler.read_input(&mut dev, listen_fd);
+
+        let passed = handler.osc_stats.received > received && check(&dev, handler);
+        if !passed {
+            failed += 1;
+        }
+
+        println!(
+            " :: self-test: {} {}",
+            name,
+            if passed { "ok" } else { "FAILED" }
+        );
+    }
+
+    if failed == 0 {
+        println!(" :: self-test passed");
+    } else {
+        println!(" :: self-test: {} checks failed", failed);
+    }
+
+    failed == 0
+}
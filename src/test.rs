@@ -0,0 +1,764 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+#[allow(unused_imports)]
+use super::*;
+
+use base::MaschineEvent;
+use maschine::transport::MockMidi;
+
+#[test]
+fn test_pad_note_top_group() {
+    // group_h, every pad still fits
+    for pad in 0..16 {
+        assert!(pad_note(108, pad, NoteRangePolicy::Drop).is_some());
+    }
+
+    // pad 3 has the highest offset (15)
+    assert_eq!(pad_note(108, 3, NoteRangePolicy::Drop), Some(123));
+    assert_eq!(pad_note(120, 3, NoteRangePolicy::Drop), None);
+    assert_eq!(pad_note(120, 3, NoteRangePolicy::Clamp), Some(127));
+    assert_eq!(pad_note(127, 12, NoteRangePolicy::Drop), Some(127));
+    assert_eq!(pad_note(255, 12, NoteRangePolicy::Clamp), Some(127));
+}
+
+#[test]
+fn test_parse_color() {
+    use osc::Argument::{f, i};
+
+    assert_eq!(parse_color(&[i(0xFF00FF)]), Some(0xFF00FF));
+    assert_eq!(parse_color(&[i(0)]), Some(0));
+    assert_eq!(parse_color(&[i(255), i(0), i(128)]), Some(0xFF0080));
+
+    // negative values used to wrap around into a huge u32 before masking
+    assert_eq!(parse_color(&[i(-1)]), None);
+    assert_eq!(parse_color(&[i(0), i(-1), i(0)]), None);
+
+    // oversized values used to be silently masked
+    assert_eq!(parse_color(&[i(0x1000000)]), None);
+    assert_eq!(parse_color(&[i(256), i(0), i(0)]), None);
+
+    assert_eq!(parse_color(&[]), None);
+    assert_eq!(parse_color(&[f(1.0)]), None);
+    assert_eq!(parse_color(&[i(1), i(2)]), None);
+}
+
+fn button_report(byte0: u8, encoder: u8) -> Vec<u8> {
+    let mut report = vec![0u8; 25];
+    report[0] = 0x01;
+    report[1] = byte0;
+    report[24] = encoder;
+    report
+}
+
+fn pad_report(pad: usize, raw: u16) -> Vec<u8> {
+    let mut report = vec![0u8; 65];
+    report[0] = 0x20;
+    report[1 + pad * 2] = (raw & 0xFF) as u8;
+    report[2 + pad * 2] = (raw >> 8) as u8;
+    report
+}
+
+#[test]
+fn test_decode_buttons() {
+    use base::MaschineButton::F1;
+    use devices::mk2::mikro::ReportDecoder;
+
+    let mut decoder = ReportDecoder::new();
+
+    // the first report only syncs the encoder position
+    assert_eq!(decoder.decode(&button_report(0, 5)), Some(vec![]));
+
+    assert_eq!(
+        decoder.decode(&button_report(0x01, 5)),
+        Some(vec![MaschineEvent::ButtonDown(F1, 0x01)])
+    );
+    assert_eq!(decoder.decode(&button_report(0x01, 5)), Some(vec![]));
+    assert_eq!(
+        decoder.decode(&button_report(0, 5)),
+        Some(vec![MaschineEvent::ButtonUp(F1, 0)])
+    );
+}
+
+#[test]
+fn test_decode_encoder() {
+    use devices::mk2::mikro::ReportDecoder;
+
+    let mut decoder = ReportDecoder::new();
+
+    assert_eq!(decoder.decode(&button_report(0, 0xF)), Some(vec![]));
+
+    // wraps from 0xF to 0x0
+    assert_eq!(
+        decoder.decode(&button_report(0, 0)),
+        Some(vec![MaschineEvent::EncoderStep(0, 1)])
+    );
+    assert_eq!(
+        decoder.decode(&button_report(0, 0xF)),
+        Some(vec![MaschineEvent::EncoderStep(0, -1)])
+    );
+}
+
+#[test]
+fn test_decode_pads() {
+    use devices::mk2::mikro::ReportDecoder;
+
+    let mut decoder = ReportDecoder::new();
+    let mut events = vec![];
+
+    // enough samples to push the median filter past the threshold and hold it there
+    for _ in 0..10 {
+        events.extend(decoder.decode(&pad_report(4, 0xFFF)).unwrap());
+    }
+
+    assert_eq!(
+        events,
+        vec![
+            MaschineEvent::PadPressed(4, 1.0),
+            MaschineEvent::PadAftertouch(4, 1.0),
+            MaschineEvent::PadAftertouch(4, 1.0),
+        ]
+    );
+
+    events.clear();
+    for _ in 0..10 {
+        events.extend(decoder.decode(&pad_report(4, 0)).unwrap());
+    }
+
+    assert_eq!(events.last(), Some(&MaschineEvent::PadReleased(4)));
+    assert_eq!(
+        events
+            .iter()
+            .filter(|ev| **ev == MaschineEvent::PadReleased(4))
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn test_decode_short_and_unknown_reports() {
+    use devices::mk2::mikro::ReportDecoder;
+
+    let mut decoder = ReportDecoder::new();
+
+    assert_eq!(decoder.decode(&[]), None);
+    assert_eq!(decoder.decode(&[0x33, 0x00]), None);
+    assert_eq!(decoder.decode(&[0x01, 0xFF]), Some(vec![]));
+    assert_eq!(decoder.decode(&[0x20, 0xFF, 0x0F]), Some(vec![]));
+}
+
+#[test]
+fn test_osc_button_name() {
+    assert_eq!(osc_button_name("/maschine/button/play"), Some("play"));
+    assert_eq!(osc_button_name("/maschine/button/play/"), Some("play"));
+    assert_eq!(osc_button_name("/maschine/buttonplay"), Some("play"));
+
+    assert_eq!(osc_button_name("/maschine/button"), None);
+    assert_eq!(osc_button_name("/maschine/button/"), None);
+    assert_eq!(osc_button_name("/maschine/but"), None);
+    assert_eq!(osc_button_name(""), None);
+}
+
+#[test]
+fn test_debounce() {
+    use base::debounce::Debounce;
+    use base::MaschineButton::Play;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let at = |ms| UNIX_EPOCH + Duration::from_millis(ms);
+    let mut debounce = Debounce::new(Duration::from_millis(5));
+
+    // a press that bounces settles down, only the first edge gets through
+    let press = [(0, true), (1, false), (2, true), (3, false), (4, true)];
+    let accepted: Vec<_> = press
+        .iter()
+        .filter(|&&(ms, down)| debounce.accept(Play, down, 0x40, at(ms)))
+        .collect();
+    assert_eq!(accepted, [&(0, true)]);
+    assert_eq!(debounce.settle(at(10)), []);
+
+    // a release that bounces settles up
+    let release = [(20, false), (21, true), (22, false)];
+    let accepted: Vec<_> = release
+        .iter()
+        .filter(|&&(ms, down)| debounce.accept(Play, down, 0x40, at(ms)))
+        .collect();
+    assert_eq!(accepted, [&(20, false)]);
+    assert_eq!(debounce.settle(at(30)), []);
+
+    // a real release inside the window is held back, not lost
+    assert!(debounce.accept(Play, true, 0x40, at(40)));
+    assert!(!debounce.accept(Play, false, 0x40, at(42)));
+    assert_eq!(debounce.settle(at(44)), []);
+    assert_eq!(
+        debounce.settle(at(45)),
+        [MaschineEvent::ButtonUp(Play, 0x40)]
+    );
+    assert!(debounce.accept(Play, false, 0x40, at(46)));
+
+    // nothing's held back with debouncing off
+    let mut debounce = Debounce::new(Duration::from_millis(0));
+    assert!(debounce.accept(Play, true, 0x40, at(0)));
+    assert!(debounce.accept(Play, false, 0x40, at(0)));
+}
+
+#[test]
+fn test_button_lights_survive_writes() {
+    use devices::mk2::Mikro;
+    use std::io::Read;
+    use std::os::unix::net::UnixStream;
+
+    // the three light reports, pads then the two button ones
+    const REPORTS: usize = 49 + 32 + 57;
+
+    let (dev, mut lights) = UnixStream::pair().unwrap();
+    let mut mikro = Mikro::new(dev.as_raw_fd());
+
+    mikro.set_button_light(MaschineButton::Play, 0xFFFFFF, 127.0);
+    mikro.set_button_light(MaschineButton::F1, 0xFFFFFF, 64.0);
+    mikro.write_lights();
+
+    // later writes for other lights still carry the buttons
+    mikro.set_pad_light(0, 0xFF0000, 1.0);
+    mikro.write_lights();
+    mikro.write_lights();
+
+    let mut written = [0u8; 3 * REPORTS];
+    lights.read_exact(&mut written).unwrap();
+
+    for reports in written.chunks(REPORTS) {
+        let (pads, buttons) = reports.split_at(49);
+        let (buttons2, buttons3) = buttons.split_at(32);
+
+        assert_eq!((pads[0], buttons2[0], buttons3[0]), (0x80, 0x82, 0x81));
+        assert_eq!(buttons2[8], 64);
+        assert_eq!(buttons3[53], 127);
+    }
+
+    assert_eq!(written[REPORTS + 1], 0x7F);
+}
+
+#[test]
+fn test_soft_tap_sends_no_note_off() {
+    use devices::mock::MockMaschine;
+
+    let midi = MockMidi::default();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut handler = test_handler(&[], Rc::new(SystemClock), &midi, &socket);
+    let mut dev = MockMaschine::default();
+
+    // too soft a touch to make a velocity plays nothing, and letting go has nothing to end
+    let soft = 0.000001;
+    assert_eq!(DEFAULT_PRESSURE_SHAPE.velocity(soft), 0);
+    handler.pad_pressed(&mut dev, 0, soft);
+    handler.pad_aftertouch(&mut dev, 0, soft);
+    handler.pad_released(&mut dev, 0);
+    assert_eq!(midi.take_sent(), []);
+
+    // while a proper hit still plays
+    handler.pad_pressed(&mut dev, 0, 0.5);
+    handler.pad_released(&mut dev, 0);
+    let sent = midi.take_sent();
+    assert!(
+        matches!(sent[..], [Message::NoteOn(Ch1, n, v), Message::NoteOff(Ch1, m, 0)] if n == m && v > 0)
+    );
+}
+
+#[test]
+fn test_humanize_is_seeded_and_bounded() {
+    use base::humanize::Humanize;
+
+    let mut a = Humanize::new(10, 1234);
+    let mut b = Humanize::new(10, 1234);
+
+    let played: Vec<_> = (0..100).map(|_| a.velocity(100)).collect();
+    assert!(played.iter().all(|&v| (90..=110).contains(&v)));
+    assert!(played.iter().any(|&v| v != 100));
+
+    // the same seed plays the same velocities
+    assert!(played.iter().all(|&v| v == b.velocity(100)));
+
+    // never pushed to a note off or past the top
+    assert!((0..100).all(|_| a.velocity(1) >= 1 && a.velocity(127) <= 127));
+    assert_eq!(a.velocity(0), 0);
+}
+
+#[test]
+fn test_release_lockout_swallows_bounce() {
+    use base::release_lockout::ReleaseLockout;
+    use devices::mk2::mikro::ReportDecoder;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let mut decoder = ReportDecoder::new();
+    let mut lockout = ReleaseLockout::new(Duration::from_millis(20));
+    let mut decoded = vec![];
+
+    // a hit, a release, then the sensor bouncing back up and down again, a report a
+    // millisecond
+    let pressures = [(0xFFF, 10), (0, 10), (0xFFF, 8), (0, 10)];
+    let mut ms = 0;
+    for &(raw, reports) in pressures.iter() {
+        for _ in 0..reports {
+            for ev in decoder.decode(&pad_report(7, raw)).unwrap() {
+                decoded.push((ev, UNIX_EPOCH + Duration::from_millis(ms)));
+            }
+            ms += 1;
+        }
+    }
+
+    let presses = |evs: &[MaschineEvent]| {
+        evs.iter()
+            .filter(|ev| matches!(ev, MaschineEvent::PadPressed(7, _)))
+            .count()
+    };
+    let releases = |evs: &[MaschineEvent]| {
+        evs.iter()
+            .filter(|&&ev| ev == MaschineEvent::PadReleased(7))
+            .count()
+    };
+
+    // the bounce really does read as a second hit
+    let all: Vec<_> = decoded.iter().map(|&(ev, _)| ev).collect();
+    assert_eq!((presses(&all), releases(&all)), (2, 2));
+
+    let accepted: Vec<_> = decoded
+        .iter()
+        .filter(|&&(ev, at)| lockout.accept(ev, at))
+        .map(|&(ev, _)| ev)
+        .collect();
+    assert_eq!((presses(&accepted), releases(&accepted)), (1, 1));
+    assert_eq!(accepted.last(), Some(&MaschineEvent::PadReleased(7)));
+
+    // once the lockout's over the pad plays again
+    let later = UNIX_EPOCH + Duration::from_millis(ms + 20);
+    assert!(lockout.accept(MaschineEvent::PadPressed(7, 1.0), later));
+    assert!(lockout.accept(MaschineEvent::PadReleased(7), later));
+
+    // and with it off, every hit gets through
+    let mut lockout = ReleaseLockout::new(Duration::ZERO);
+    assert!(lockout.accept(MaschineEvent::PadReleased(7), UNIX_EPOCH));
+    assert!(lockout.accept(MaschineEvent::PadPressed(7, 1.0), UNIX_EPOCH));
+}
+
+// a handler started with `args` whose MIDI goes to `midi`, listening on `socket` and sending
+// OSC nowhere
+fn test_handler<'a>(
+    args: &[&str],
+    clock: Rc<dyn Clock>,
+    midi: &'a MockMidi,
+    socket: &'a UdpSocket,
+) -> MHandler<'a> {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let config = Config::from_env_and_args(&[], &args).unwrap();
+    let pressure_shape = load_pressure_shape(&config).unwrap();
+
+    let mut handler = MHandler::new(
+        Rc::new(config),
+        vec![],
+        args,
+        pressure_shape,
+        clock,
+        midi,
+        socket,
+    );
+    handler.osc_outgoing_addrs.clear();
+    handler
+}
+
+// sends the message to the handler's socket the way a client would, and has it read it
+fn send_to_handler(handler: &mut MHandler, dev: &mut dyn Maschine, msg: osc::Message) {
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = handler.osc_socket.local_addr().unwrap();
+    sender.send_to(&msg.serialize().unwrap(), addr).unwrap();
+
+    let fd = handler.osc_socket.as_raw_fd();
+    handler.read_input(dev, fd);
+}
+
+#[test]
+fn test_long_osc_messages_arrive_whole() {
+    use devices::mock::MockMaschine;
+
+    let midi = MockMidi::default();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut handler = test_handler(&[], Rc::new(SystemClock), &midi, &socket);
+    let mut dev = MockMaschine::default();
+
+    // a full frame of lights, well over a couple of hundred bytes
+    let mut frame = vec![];
+    for pad in 0..16 {
+        frame.push(osc::Argument::i(pad * 0x10));
+        frame.push(osc::Argument::f(0.5));
+    }
+    frame.extend(osc_args![
+        "play", 0xFFFFFF, 127.0f32, "rec", 0xFF0000, 64.0f32
+    ]);
+
+    let msg = osc::Message {
+        path: "/maschine/leds/set",
+        arguments: frame,
+    };
+    assert!(msg.serialize().unwrap().len() > 184);
+    send_to_handler(&mut handler, &mut dev, msg);
+
+    assert_eq!(handler.osc_stats.malformed, 0);
+    assert_eq!(handler.osc_stats.handled, 1);
+    assert!((0..16).all(|pad| dev.pad_lights[pad] == (pad as u32 * 0x10, 0.5)));
+    assert_eq!(
+        dev.button_light(MaschineButton::Play),
+        Some((0xFFFFFF, 127.0))
+    );
+    assert_eq!(
+        dev.button_light(MaschineButton::Rec),
+        Some((0xFF0000, 64.0))
+    );
+
+    // and a sysex a few hundred bytes long goes out in one piece
+    let mut sysex = osc_args![0xF0, 0x7D];
+    sysex.extend((0..300).map(|i| osc::Argument::i(i % 0x80)));
+    sysex.push(osc::Argument::i(0xF7));
+
+    let msg = osc::Message {
+        path: "/maschine/sysex",
+        arguments: sysex,
+    };
+    send_to_handler(&mut handler, &mut dev, msg);
+
+    let data: Vec<U7> = (0..300).map(|i| (i % 0x80) as U7).collect();
+    assert_eq!(handler.osc_stats.malformed, 0);
+    assert_eq!(
+        midi.take_sent(),
+        [Message::SysEx(Manufacturer::OneByte(0x7D), data)]
+    );
+}
+
+#[test]
+fn test_shared_note_ends_with_last_pad() {
+    use devices::mock::MockMaschine;
+
+    // pads 0 and 5 both play note 36
+    let notes = "36,37,38,39,40,36,42,43,44,45,46,47,48,49,50,51";
+
+    let midi = MockMidi::default();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut handler = test_handler(
+        &["--pad-notes", notes],
+        Rc::new(SystemClock),
+        &midi,
+        &socket,
+    );
+    let mut dev = MockMaschine::default();
+
+    handler.pad_pressed(&mut dev, 0, 0.5);
+    handler.pad_pressed(&mut dev, 5, 0.5);
+    let sent = midi.take_sent();
+    assert_eq!(sent.len(), 2);
+    assert!(sent
+        .iter()
+        .all(|msg| matches!(*msg, Message::NoteOn(Ch1, 36, _))));
+
+    // letting go of pad 0 leaves pad 5 holding it, so no note off
+    handler.pad_released(&mut dev, 0);
+    assert_eq!(midi.take_sent(), []);
+
+    // letting go of pad 5 as well ends it
+    handler.pad_released(&mut dev, 5);
+    assert_eq!(midi.take_sent(), [Message::NoteOff(Ch1, 36, 0)]);
+
+    // and with --shared-notes each, every release ends it
+    let midi = MockMidi::default();
+    let args = ["--pad-notes", notes, "--shared-notes", "each"];
+    let mut handler = test_handler(&args, Rc::new(SystemClock), &midi, &socket);
+
+    handler.pad_pressed(&mut dev, 0, 0.5);
+    handler.pad_pressed(&mut dev, 5, 0.5);
+    midi.take_sent();
+
+    handler.pad_released(&mut dev, 0);
+    assert_eq!(midi.take_sent(), [Message::NoteOff(Ch1, 36, 0)]);
+    handler.pad_released(&mut dev, 5);
+    assert_eq!(midi.take_sent(), [Message::NoteOff(Ch1, 36, 0)]);
+}
+
+#[test]
+fn test_timing_follows_the_clock() {
+    use base::MockClock;
+    use devices::mock::MockMaschine;
+    use std::time::Duration;
+
+    let ms = Duration::from_millis;
+    let midi = MockMidi::default();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut dev = MockMaschine::default();
+
+    // --max-hold lets go of a pad once it's been held too long, and not before
+    let clock = Rc::new(MockClock::default());
+    let args = ["--max-hold", "500"];
+    let mut handler = test_handler(&args, clock.clone(), &midi, &socket);
+
+    handler.pad_pressed(&mut dev, 0, 0.5);
+    let note = match midi.take_sent()[..] {
+        [Message::NoteOn(Ch1, note, _)] => note,
+        ref sent => panic!("expected a note on, got {:?}", sent),
+    };
+
+    clock.advance(ms(500));
+    handler.tick(&mut dev);
+    assert_eq!(midi.take_sent(), []);
+
+    clock.advance(ms(1));
+    handler.tick(&mut dev);
+    assert_eq!(midi.take_sent(), [Message::NoteOff(Ch1, note, 0)]);
+
+    // --min-note holds back the note off of a tap until it's had its length
+    let clock = Rc::new(MockClock::default());
+    let args = ["--min-note", "50"];
+    let mut handler = test_handler(&args, clock.clone(), &midi, &socket);
+
+    handler.pad_pressed(&mut dev, 0, 0.5);
+    midi.take_sent();
+    clock.advance(ms(20));
+    handler.pad_released(&mut dev, 0);
+    handler.tick(&mut dev);
+    assert_eq!(midi.take_sent(), []);
+
+    clock.advance(ms(30));
+    handler.tick(&mut dev);
+    assert_eq!(midi.take_sent(), [Message::NoteOff(Ch1, note, 0)]);
+
+    // --release-lockout drops a hit bouncing back in right after a release
+    let clock = Rc::new(MockClock::default());
+    let args = ["--release-lockout", "20"];
+    let mut handler = test_handler(&args, clock.clone(), &midi, &socket);
+
+    handler.pad_pressed(&mut dev, 0, 0.5);
+    handler.pad_released(&mut dev, 0);
+    assert_eq!(midi.take_sent().len(), 2);
+
+    clock.advance(ms(10));
+    handler.pad_pressed(&mut dev, 0, 0.5);
+    handler.pad_released(&mut dev, 0);
+    assert_eq!(midi.take_sent(), []);
+
+    // while one after it's over plays
+    clock.advance(ms(20));
+    handler.pad_pressed(&mut dev, 0, 0.5);
+    assert!(matches!(midi.take_sent()[..], [Message::NoteOn(Ch1, n, _)] if n == note));
+}
+
+#[test]
+fn test_mikro_fades_follow_the_clock() {
+    use base::MockClock;
+    use devices::mk2::Mikro;
+    use std::io::Read;
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+
+    const REPORTS: usize = 49 + 32 + 57;
+
+    let (dev, mut lights) = UnixStream::pair().unwrap();
+    let clock = Rc::new(MockClock::default());
+    let mut mikro = Mikro::new(dev.as_raw_fd());
+    mikro.set_clock(clock.clone());
+    mikro.set_led_fade(Duration::from_millis(100));
+
+    // play comes on over the fade, a step for each write as far as the clock's moved
+    mikro.set_button_light(MaschineButton::Play, 0xFFFFFF, 127.0);
+    let mut shown = vec![];
+    for _ in 0..3 {
+        mikro.write_lights();
+
+        let mut written = [0u8; REPORTS];
+        lights.read_exact(&mut written).unwrap();
+        shown.push(written[49 + 32 + 53]);
+
+        clock.advance(Duration::from_millis(50));
+    }
+
+    assert_eq!(shown, [0, 63, 127]);
+    assert!(!mikro.lights_dirty());
+}
+
+#[test]
+fn test_encoder_push_centres_the_value() {
+    use devices::mock::MockMaschine;
+
+    let midi = MockMidi::default();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut dev = MockMaschine::default();
+
+    let args = ["--encoder-mode", "clamp"];
+    let mut handler = test_handler(&args, Rc::new(SystemClock), &midi, &socket);
+
+    handler.encoder_step(&mut dev, 0, 10);
+    assert_eq!(handler.encoder_value, 10);
+
+    // the encoder pushed down reports as nav
+    handler.button_down(&mut dev, MaschineButton::Nav, 0);
+    handler.button_up(&mut dev, MaschineButton::Nav, 0);
+    assert_eq!(handler.encoder_value, 64);
+
+    // a list goes back to its middle entry, which goes out over MIDI like a turn would
+    let args = [
+        "--encoder-mode",
+        "list-clamp",
+        "--encoder-values",
+        "0,8,16,24,32",
+        "--encoder-midi",
+        "74",
+    ];
+    let mut handler = test_handler(&args, Rc::new(SystemClock), &midi, &socket);
+
+    handler.encoder_step(&mut dev, 0, 4);
+    assert_eq!(midi.take_sent(), [Message::ControlChange(Ch1, 74, 32)]);
+
+    handler.button_down(&mut dev, MaschineButton::Nav, 0);
+    assert_eq!(handler.encoder_value, 16);
+    assert_eq!(midi.take_sent(), [Message::ControlChange(Ch1, 74, 16)]);
+}
+
+#[test]
+fn test_config_precedence() {
+    use std::fs;
+    use std::time::Duration;
+
+    let strings = |list: &[&str]| -> Vec<String> { list.iter().map(|s| s.to_string()).collect() };
+    let lockout = |vars: &[(&str, &str)], args: &[&str]| {
+        let vars: Vec<_> = vars
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Config::from_env_and_args(&vars, &strings(args)).map(|config| config.release_lockout)
+    };
+
+    let path = env::temp_dir().join(format!("maschine-test-{}.conf", process::id()));
+    fs::write(&path, "release-lockout 20\n").unwrap();
+    let path = path.to_str().unwrap();
+
+    let env = ("MASCHINE_RELEASE_LOCKOUT", "10");
+    let file = ("MASCHINE_CONFIG", path);
+    let ms = Duration::from_millis;
+
+    // the environment wins over the defaults, the file over the environment and the command
+    // line over both
+    assert_eq!(lockout(&[], &[]), Ok(ms(0)));
+    assert_eq!(lockout(&[env], &[]), Ok(ms(10)));
+    assert_eq!(lockout(&[env, file], &[]), Ok(ms(20)));
+    assert_eq!(lockout(&[env], &["--config", path]), Ok(ms(20)));
+    assert_eq!(
+        lockout(&[env, file], &["--release-lockout", "30"]),
+        Ok(ms(30))
+    );
+
+    // other programs' variables are left alone, while a bad value for ours is still an error
+    assert_eq!(lockout(&[env, ("MASCHINE_HOME", "/opt")], &[]), Ok(ms(10)));
+    assert!(lockout(&[("MASCHINE_RELEASE_LOCKOUT", "soon")], &[]).is_err());
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_parse_config_file() {
+    use config::parse_config_file;
+
+    let option = |line, name: &str, value: &str| (line, name.to_string(), value.to_string());
+
+    let text = "# a comment\n\
+                \n\
+                coalesce\n\
+                --osc-send=9000\n\
+                pad-channels 10\n  \
+                debounce \t 5  \n\
+                release-lockout\u{a0}20\n\
+                # and another\n";
+    assert_eq!(
+        parse_config_file(text),
+        Ok(vec![
+            option(3, "coalesce", "on"),
+            option(4, "osc-send", "9000"),
+            option(5, "pad-channels", "10"),
+            option(6, "debounce", "5"),
+            option(7, "release-lockout", "20"),
+        ])
+    );
+
+    assert!(parse_config_file("osc-send\n").is_err());
+    assert!(parse_config_file("config other.conf\n").is_err());
+    assert_eq!(parse_config_file(""), Ok(vec![]));
+}
+
+#[test]
+fn test_full_midi_output_drains_and_retries() {
+    use devices::mock::MockMaschine;
+
+    let midi = MockMidi::default();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut handler = test_handler(&[], Rc::new(SystemClock), &midi, &socket);
+    let mut dev = MockMaschine::default();
+
+    // batched up, nothing's written until the end of the pass
+    handler.pad_pressed(&mut dev, 0, 0.5);
+    assert_eq!(midi.drains.get(), 0);
+
+    // a send that doesn't fit writes out what's queued and goes in after it
+    midi.failing.set(1);
+    handler.pad_pressed(&mut dev, 1, 0.5);
+    assert_eq!(midi.drains.get(), 1);
+    assert_eq!(midi.take_sent().len(), 2);
+
+    // and one that still doesn't is dropped and counted
+    handler.pad_pressed(&mut dev, 2, 0.5);
+    midi.failing.set(2);
+    handler.pad_pressed(&mut dev, 3, 0.5);
+    assert_eq!(midi.take_sent().len(), 1);
+    assert_eq!(handler.midi_stats.get().messages, 3);
+    assert_eq!(handler.midi_stats.get().dropped, 1);
+}
+
+#[test]
+fn test_full_redraw_resends_the_first_byte() {
+    use devices::mk2::Mikro;
+    use std::io::Read;
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+
+    const REPORT: usize = 1 + 8 + 512;
+
+    let (dev, mut screen) = UnixStream::pair().unwrap();
+
+    // a full redraw is more than the socket holds, so it has to be read as it goes
+    let reader = thread::spawn(move || {
+        let mut written = vec![];
+        screen.read_to_end(&mut written).unwrap();
+        written
+    });
+
+    let mut mikro = Mikro::new(dev.as_raw_fd());
+    mikro.get_screen_mut()[0] = 0xFF;
+    mikro.send_screen();
+    mikro.send_screen();
+    drop(dev);
+
+    let written = reader.join().unwrap();
+    let reports: Vec<_> = written.chunks(REPORT).collect();
+    let byte = |report: &[u8]| (report[1] as usize + report[3] as usize * 32, report[9]);
+
+    // the redraw's first report goes out blank, and the next send fills it in
+    assert_eq!(byte(reports[0]), (0, 0));
+    assert_eq!(reports.len(), 4097 + 1);
+    assert_eq!(byte(reports[4097]), (0, 0xFF));
+}
@@ -0,0 +1,110 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+use std::os::unix::io::RawFd;
+
+use alsa_seq::*;
+use midi::Message;
+
+use transport::MidiPorts;
+
+// both directions get a client of their own, named the same
+pub struct AlsaSequencer {
+    output: SequencerHandle,
+    input: SequencerHandle,
+}
+
+impl AlsaSequencer {
+    pub fn open(client_name: &str) -> Result<Self, String> {
+        let unavailable = |_| "ALSA sequencer unavailable, is snd-seq loaded?".to_string();
+
+        Ok(AlsaSequencer {
+            output: SequencerHandle::open(client_name, HandleOpenStreams::Output)
+                .map_err(unavailable)?,
+            input: SequencerHandle::open(client_name, HandleOpenStreams::Input)
+                .map_err(unavailable)?,
+        })
+    }
+
+    pub fn create_ports<'a>(
+        &'a self,
+        out_port: &str,
+        in_port: &str,
+    ) -> Result<AlsaMidi<'a>, String> {
+        let port = self
+            .output
+            .create_port(
+                out_port,
+                PortCapabilities::PORT_CAPABILITY_READ
+                    | PortCapabilities::PORT_CAPABILITY_SUBS_READ,
+                PortType::MidiGeneric,
+            )
+            .map_err(|_| format!("couldn't create the MIDI port \"{}\"", out_port))?;
+        let port_in = self
+            .input
+            .create_port(
+                in_port,
+                PortCapabilities::PORT_CAPABILITY_WRITE
+                    | PortCapabilities::PORT_CAPABILITY_SUBS_WRITE,
+                PortType::MidiGeneric,
+            )
+            .map_err(|_| format!("couldn't create the MIDI port \"{}\"", in_port))?;
+
+        Ok(AlsaMidi {
+            seq: self,
+            port,
+            _port_in: port_in,
+        })
+    }
+}
+
+pub struct AlsaMidi<'a> {
+    seq: &'a AlsaSequencer,
+    port: SequencerPort<'a>,
+    // input arrives on the handle rather than the port, this only has to exist
+    _port_in: SequencerPort<'a>,
+}
+
+impl<'a> MidiPorts for AlsaMidi<'a> {
+    fn send(&self, msg: &Message) -> Result<(), ()> {
+        self.port.send_message(msg).map_err(|_| ())
+    }
+
+    fn drain(&self) {
+        self.seq.output.drain_output();
+    }
+
+    fn input_fd(&self) -> Option<RawFd> {
+        self.seq.input.input_fd()
+    }
+
+    fn pass_thru(&self, kinds: &[EventKind]) {
+        let own_clients = [self.seq.output.client_id(), self.seq.input.client_id()];
+
+        while let Some(ev) = self.seq.input.input_event() {
+            if own_clients.contains(&ev.source_client()) || !kinds.contains(&ev.kind()) {
+                continue;
+            }
+
+            if self.port.send_event(&ev).is_err() {
+                println!(" :: couldn't pass through {:?} event", ev.kind());
+            }
+        }
+
+        self.drain();
+    }
+}
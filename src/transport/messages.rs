@@ -0,0 +1,490 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+// what the controls' OSC paths and arguments are called, and which MIDI messages they become
+
+use base::{LightFrame, MaschineButton};
+use hsl::HSL;
+use midi::{Manufacturer, Message, U14};
+use tinyosc as osc;
+
+// a colour is either a single 0xRRGGBB int or separate red, green and blue ints, all of
+// which have to be in range rather than being masked into it
+pub fn parse_color(args: &[osc::Argument]) -> Option<u32> {
+    match *args {
+        [osc::Argument::i(rgb @ 0..=0xFFFFFF)] => Some(rgb as u32),
+
+        [osc::Argument::i(r @ 0..=255), osc::Argument::i(g @ 0..=255), osc::Argument::i(b @ 0..=255)] => {
+            Some(((r as u32) << 16) | ((g as u32) << 8) | (b as u32))
+        }
+
+        _ => None,
+    }
+}
+
+// a colour as parse_color() takes it, or as hue (0-360), saturation and lightness (0-1)
+pub fn parse_hsl(args: &[osc::Argument]) -> Option<HSL> {
+    if let Some(rgb) = parse_color(args) {
+        return Some(HSL::from_rgb(&[
+            (rgb >> 16) as u8,
+            (rgb >> 8) as u8,
+            rgb as u8,
+        ]));
+    }
+
+    match *args {
+        [osc::Argument::f(h), osc::Argument::f(s), osc::Argument::f(l)] => Some(HSL {
+            h: h as f64,
+            s: s as f64,
+            l: l as f64,
+        }),
+        _ => None,
+    }
+}
+
+// a frame as /maschine/leds sends it: a colour and brightness for each pad, then any number of
+// buttons by name with theirs
+pub fn parse_light_frame(args: &[osc::Argument]) -> Option<LightFrame> {
+    if args.len() < 32 || !(args.len() - 32).is_multiple_of(3) {
+        return None;
+    }
+
+    let mut frame = LightFrame::default();
+
+    for (pad, light) in args[..32].chunks(2).enumerate() {
+        frame.pads[pad] = match *light {
+            [osc::Argument::i(color @ 0..=0xFFFFFF), osc::Argument::f(brightness)] => {
+                (color as u32, brightness)
+            }
+            _ => return None,
+        };
+    }
+
+    for light in args[32..].chunks(3) {
+        match *light {
+            [osc::Argument::s(name), osc::Argument::i(color @ 0..=0xFFFFFF), osc::Argument::f(brightness)] => {
+                frame.set_button(osc_button_to_btn_map(name)?, color as u32, brightness)
+            }
+            _ => return None,
+        }
+    }
+
+    Some(frame)
+}
+
+// a sysex message sent as one int per byte, F0 and F7 included. the manufacturer id is one
+// byte, or three when the first is 0
+pub fn parse_sysex(args: &[osc::Argument]) -> Option<Message> {
+    let mut bytes = Vec::with_capacity(args.len());
+
+    for arg in args {
+        match *arg {
+            osc::Argument::i(byte @ 0..=255) => bytes.push(byte as u8),
+            _ => return None,
+        }
+    }
+
+    let (&first, rest) = bytes.split_first()?;
+    let (&last, body) = rest.split_last()?;
+
+    if first != 0xF0 || last != 0xF7 || body.iter().any(|&b| b > 0x7F) {
+        return None;
+    }
+
+    match *body {
+        [0x00, b2, b3, ref data @ ..] => Some(Message::SysEx(
+            Manufacturer::ThreeByte(0x00, b2, b3),
+            data.to_vec(),
+        )),
+        [0x00, ..] => None,
+        [id, ref data @ ..] => Some(Message::SysEx(Manufacturer::OneByte(id), data.to_vec())),
+        [] => None,
+    }
+}
+
+// the button name from "/maschine/button/<name>", with or without the separating slash
+pub fn osc_button_name(path: &str) -> Option<&str> {
+    let name = path.strip_prefix("/maschine/button")?;
+    let name = name.strip_prefix('/').unwrap_or(name);
+    let name = name.strip_suffix('/').unwrap_or(name);
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+// the pad in /maschine/pad/<n>/<what>
+pub fn osc_pad_path(path: &str, what: &str) -> Option<usize> {
+    let pad = path.strip_prefix("/maschine/pad/")?;
+    let pad = pad.strip_suffix(what)?.strip_suffix('/')?;
+
+    match pad.parse::<usize>() {
+        Ok(pad @ 0..=15) => Some(pad),
+        _ => None,
+    }
+}
+
+pub fn osc_button_to_btn_map(osc_button: &str) -> Option<MaschineButton> {
+    match osc_button {
+        "restart" => Some(MaschineButton::Restart),
+        "step_left" => Some(MaschineButton::Stepleft),
+        "step_right" => Some(MaschineButton::Stepright),
+        "grid" => Some(MaschineButton::Grid),
+        "play" => Some(MaschineButton::Play),
+        "rec" => Some(MaschineButton::Rec),
+        // still taken from clients written for when erase was called stop
+        "erase" | "stop" => Some(MaschineButton::Erase),
+        "shift" => Some(MaschineButton::Shift),
+
+        "browse" => Some(MaschineButton::Browse),
+        "sampling" => Some(MaschineButton::Sampling),
+        "note_repeat" => Some(MaschineButton::Noterepeat),
+
+        "encoder" => Some(MaschineButton::Encoder),
+
+        "f1" => Some(MaschineButton::F1),
+        "f2" => Some(MaschineButton::F2),
+        "f3" => Some(MaschineButton::F3),
+        "f4" => Some(MaschineButton::F4),
+        "f5" => Some(MaschineButton::F5),
+        "f6" => Some(MaschineButton::F6),
+        "f7" => Some(MaschineButton::F7),
+        "f8" => Some(MaschineButton::F8),
+
+        "swing" => Some(MaschineButton::Swing),
+        "step" => Some(MaschineButton::Step),
+        "volume" => Some(MaschineButton::Volume),
+
+        "enter" => Some(MaschineButton::Enter),
+        "auto" => Some(MaschineButton::Auto),
+        "all" => Some(MaschineButton::All),
+        "navigate" => Some(MaschineButton::Navigate),
+        "tempo" => Some(MaschineButton::Tempo),
+
+        "control" => Some(MaschineButton::Control),
+        "nav" => Some(MaschineButton::Nav),
+        "nav_left" => Some(MaschineButton::Navleft),
+        "nav_right" => Some(MaschineButton::Navright),
+        "main" => Some(MaschineButton::Main),
+
+        "scene" => Some(MaschineButton::Scene),
+        "pattern" => Some(MaschineButton::Pattern),
+        "pad_mode" => Some(MaschineButton::Padmode),
+        "view" => Some(MaschineButton::View),
+        "duplicate" => Some(MaschineButton::Duplicate),
+        "select" => Some(MaschineButton::Select),
+        "solo" => Some(MaschineButton::Solo),
+        "mute" => Some(MaschineButton::Mute),
+
+        "group_a" => Some(MaschineButton::GroupA),
+        "group_b" => Some(MaschineButton::GroupB),
+        "group_c" => Some(MaschineButton::GroupC),
+        "group_d" => Some(MaschineButton::GroupD),
+        "group_e" => Some(MaschineButton::GroupE),
+        "group_f" => Some(MaschineButton::GroupF),
+        "group_g" => Some(MaschineButton::GroupG),
+        "group_h" => Some(MaschineButton::GroupH),
+
+        "page_right" => Some(MaschineButton::Pageright),
+        "page_left" => Some(MaschineButton::Pageleft),
+
+        _ => None,
+    }
+}
+
+// the RPN a button sends, and whether that's only on press or on release as well
+pub fn button_rpn(button: &str) -> Option<(U14, bool)> {
+    const CONTROL_BASE: U14 = 40;
+
+    let rpn = match button {
+        "play" => 1,
+        "stop" => 2,
+        "rec" => 3,
+        "grid" => 4,
+        "step_left" => 5,
+        "step_right" => 6,
+        "restart" => 7,
+        "browse" => 8,
+        "sampling" => 9,
+        "note_repeat" => 10,
+        "control" => 11,
+        "nav" => 12,
+        "nav_left" => 13,
+        "nav_right" => 14,
+        "main" => 15,
+        "scene" => 16,
+        "pattern" => 17,
+        "pad_mode" => 18,
+        "view" => 19,
+        "duplicate" => 20,
+        "select" => 21,
+        "solo" => 22,
+        "step" => 23,
+        "mute" => 24,
+        "navigate" => 25,
+        "tempo" => 26,
+        "enter" => 27,
+        "auto" => 28,
+        "all" => 29,
+        "f1" => 30,
+        "f2" => 31,
+        "f3" => 32,
+        "f4" => 33,
+        "f5" => 34,
+        "f6" => 35,
+        "f7" => 36,
+        "f8" => 37,
+        "page_right" => 38,
+        "page_left" => 39,
+
+        // past the controls, erase only got its own when it stopped being stop
+        "erase" => 49,
+
+        "A8" => return Some((CONTROL_BASE, false)),
+        "B5" | "B6" | "B7" | "B8" | "C8" => return Some((CONTROL_BASE + 1, false)),
+        "D5" | "D6" | "D7" | "D8" | "E8" => return Some((CONTROL_BASE + 2, false)),
+        "FF5" | "FF6" | "FF7" | "FF8" | "G8" => return Some((CONTROL_BASE + 3, false)),
+        "H5" | "H6" | "H7" | "H8" | "I8" => return Some((CONTROL_BASE + 4, false)),
+        "J5" | "J6" | "J7" | "J8" | "K8" => return Some((CONTROL_BASE + 5, false)),
+        "L5" | "L6" | "L7" | "L8" | "M8" => return Some((CONTROL_BASE + 6, false)),
+        "N5" | "N6" | "N7" | "N8" | "O8" => return Some((CONTROL_BASE + 7, false)),
+        "P5" | "P6" => return Some((CONTROL_BASE + 8, false)),
+
+        _ => return None,
+    };
+
+    Some((rpn, true))
+}
+
+pub fn btn_to_osc_button_map(btn: MaschineButton) -> &'static str {
+    match btn {
+        MaschineButton::Restart => "restart",
+        MaschineButton::Stepleft => "step_left",
+        MaschineButton::Stepright => "step_right",
+        MaschineButton::Grid => "grid",
+        MaschineButton::Play => "play",
+        MaschineButton::Rec => "rec",
+        MaschineButton::Erase => "erase",
+        MaschineButton::Shift => "shift",
+
+        MaschineButton::Browse => "browse",
+        MaschineButton::Sampling => "sampling",
+        MaschineButton::Noterepeat => "note_repeat",
+
+        MaschineButton::Encoder => "encoder",
+
+        MaschineButton::F1 => "f1",
+        MaschineButton::F2 => "f2",
+        MaschineButton::F3 => "f3",
+        MaschineButton::F4 => "f4",
+        MaschineButton::F5 => "f5",
+        MaschineButton::F6 => "f6",
+        MaschineButton::F7 => "f7",
+        MaschineButton::F8 => "f8",
+
+        MaschineButton::Swing => "swing",
+        MaschineButton::Step => "step",
+        MaschineButton::Volume => "volume",
+
+        MaschineButton::Enter => "enter",
+        MaschineButton::Auto => "auto",
+        MaschineButton::All => "all",
+        MaschineButton::Navigate => "navigate",
+        MaschineButton::Tempo => "tempo",
+
+        MaschineButton::Control => "control",
+        MaschineButton::Nav => "nav",
+        MaschineButton::Navleft => "nav_left",
+        MaschineButton::Navright => "nav_right",
+        MaschineButton::Main => "main",
+
+        MaschineButton::Scene => "scene",
+        MaschineButton::Pattern => "pattern",
+        MaschineButton::Padmode => "pad_mode",
+        MaschineButton::View => "view",
+        MaschineButton::Duplicate => "duplicate",
+        MaschineButton::Select => "select",
+        MaschineButton::Solo => "solo",
+        MaschineButton::Mute => "mute",
+
+        MaschineButton::GroupA => "group_a",
+        MaschineButton::GroupB => "group_b",
+        MaschineButton::GroupC => "group_c",
+        MaschineButton::GroupD => "group_d",
+        MaschineButton::GroupE => "group_e",
+        MaschineButton::GroupF => "group_f",
+        MaschineButton::GroupG => "group_g",
+        MaschineButton::GroupH => "group_h",
+
+        MaschineButton::Pageright => "page_right",
+        MaschineButton::Pageleft => "page_left",
+        MaschineButton::R1 => "R1",
+        MaschineButton::R2 => "R2",
+        MaschineButton::R3 => "R3",
+        MaschineButton::R4 => "R4",
+        MaschineButton::R5 => "R5",
+        MaschineButton::R6 => "R6",
+        MaschineButton::R7 => "R7",
+        MaschineButton::R8 => "R8",
+
+        MaschineButton::A1 => "A1",
+        MaschineButton::A2 => "A2",
+        MaschineButton::A3 => "A3",
+        MaschineButton::A4 => "A4",
+        MaschineButton::A5 => "A5",
+        MaschineButton::A6 => "A6",
+        MaschineButton::A7 => "A7",
+        MaschineButton::A8 => "A8",
+
+        MaschineButton::B1 => "B1",
+        MaschineButton::B2 => "B2",
+        MaschineButton::B3 => "B3",
+        MaschineButton::B4 => "B4",
+        MaschineButton::B5 => "B5",
+        MaschineButton::B6 => "B6",
+        MaschineButton::B7 => "B7",
+        MaschineButton::B8 => "B8",
+
+        MaschineButton::C1 => "C1",
+        MaschineButton::C2 => "C2",
+        MaschineButton::C3 => "C3",
+        MaschineButton::C4 => "C4",
+        MaschineButton::C5 => "C5",
+        MaschineButton::C6 => "C6",
+        MaschineButton::C7 => "C7",
+        MaschineButton::C8 => "C8",
+
+        MaschineButton::D1 => "D1",
+        MaschineButton::D2 => "D2",
+        MaschineButton::D3 => "D3",
+        MaschineButton::D4 => "D4",
+        MaschineButton::D5 => "D5",
+        MaschineButton::D6 => "D6",
+        MaschineButton::D7 => "D7",
+        MaschineButton::D8 => "D8",
+
+        MaschineButton::E1 => "E1",
+        MaschineButton::E2 => "E2",
+        MaschineButton::E3 => "E3",
+        MaschineButton::E4 => "E4",
+        MaschineButton::E5 => "E5",
+        MaschineButton::E6 => "E6",
+        MaschineButton::E7 => "E7",
+        MaschineButton::E8 => "E8",
+
+        MaschineButton::FF1 => "FF1",
+        MaschineButton::FF2 => "FF2",
+        MaschineButton::FF3 => "FF3",
+        MaschineButton::FF4 => "FF4",
+        MaschineButton::FF5 => "FF5",
+        MaschineButton::FF6 => "FF6",
+        MaschineButton::FF7 => "FF8",
+        MaschineButton::FF8 => "FF8",
+
+        MaschineButton::G1 => "G1",
+        MaschineButton::G2 => "G2",
+        MaschineButton::G3 => "G3",
+        MaschineButton::G4 => "G4",
+        MaschineButton::G5 => "G5",
+        MaschineButton::G6 => "G6",
+        MaschineButton::G7 => "G7",
+        MaschineButton::G8 => "G8",
+
+        MaschineButton::H1 => "H1",
+        MaschineButton::H2 => "H2",
+        MaschineButton::H3 => "H3",
+        MaschineButton::H4 => "H4",
+        MaschineButton::H5 => "H5",
+        MaschineButton::H6 => "H6",
+        MaschineButton::H7 => "H7",
+        MaschineButton::H8 => "H8",
+
+        MaschineButton::I1 => "I1",
+        MaschineButton::I2 => "I2",
+        MaschineButton::I3 => "I3",
+        MaschineButton::I4 => "I4",
+        MaschineButton::I5 => "I5",
+        MaschineButton::I6 => "I6",
+        MaschineButton::I7 => "I7",
+        MaschineButton::I8 => "I8",
+
+        MaschineButton::J1 => "J1",
+        MaschineButton::J2 => "J2",
+        MaschineButton::J3 => "J3",
+        MaschineButton::J4 => "J4",
+        MaschineButton::J5 => "J5",
+        MaschineButton::J6 => "J6",
+        MaschineButton::J7 => "J7",
+        MaschineButton::J8 => "J8",
+
+        MaschineButton::K1 => "K1",
+        MaschineButton::K2 => "K2",
+        MaschineButton::K3 => "K3",
+        MaschineButton::K4 => "K4",
+        MaschineButton::K5 => "K5",
+        MaschineButton::K6 => "K6",
+        MaschineButton::K7 => "K7",
+        MaschineButton::K8 => "K8",
+
+        MaschineButton::L1 => "L1",
+        MaschineButton::L2 => "L2",
+        MaschineButton::L3 => "L3",
+        MaschineButton::L4 => "L4",
+        MaschineButton::L5 => "L5",
+        MaschineButton::L6 => "L6",
+        MaschineButton::L7 => "L7",
+        MaschineButton::L8 => "L8",
+
+        MaschineButton::M1 => "M1",
+        MaschineButton::M2 => "M2",
+        MaschineButton::M3 => "M3",
+        MaschineButton::M4 => "M4",
+        MaschineButton::M5 => "M5",
+        MaschineButton::M6 => "M6",
+        MaschineButton::M7 => "M7",
+        MaschineButton::M8 => "M8",
+
+        MaschineButton::N1 => "N1",
+        MaschineButton::N2 => "N2",
+        MaschineButton::N3 => "N3",
+        MaschineButton::N4 => "N4",
+        MaschineButton::N5 => "N5",
+        MaschineButton::N6 => "N6",
+        MaschineButton::N7 => "N7",
+        MaschineButton::N8 => "N8",
+
+        MaschineButton::O1 => "O1",
+        MaschineButton::O2 => "O2",
+        MaschineButton::O3 => "O3",
+        MaschineButton::O4 => "O4",
+        MaschineButton::O5 => "O5",
+        MaschineButton::O6 => "O6",
+        MaschineButton::O7 => "O7",
+        MaschineButton::O8 => "O8",
+
+        MaschineButton::P1 => "P1",
+        MaschineButton::P2 => "P2",
+        MaschineButton::P3 => "P3",
+        MaschineButton::P4 => "P4",
+        MaschineButton::P5 => "P5",
+        MaschineButton::P6 => "P6",
+        MaschineButton::P7 => "P7",
+        MaschineButton::P8 => "P8",
+    }
+}
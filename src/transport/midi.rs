@@ -0,0 +1,38 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+use std::os::unix::io::RawFd;
+
+use alsa_seq::EventKind;
+use midi::Message;
+
+// where a handler's MIDI goes out and comes in, so it can be driven without a sequencer
+pub trait MidiPorts {
+    // queues a message on the output port, to go out with the next drain(). Err if it
+    // couldn't be queued
+    #[allow(clippy::result_unit_err)]
+    fn send(&self, msg: &Message) -> Result<(), ()>;
+    fn drain(&self);
+
+    // polls readable when there's input for pass_thru(), None without an input port
+    fn input_fd(&self) -> Option<RawFd>;
+
+    // sends what's arrived on the input port straight back out of the output port, the kinds
+    // of event asked for only. anything we sent ourselves is dropped, so connecting the
+    // output back to the input can't make it go round forever
+    fn pass_thru(&self, kinds: &[EventKind]);
+}
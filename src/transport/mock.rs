@@ -0,0 +1,64 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+use std::cell::{Cell, RefCell};
+use std::os::unix::io::RawFd;
+
+use alsa_seq::EventKind;
+use midi::Message;
+
+use transport::MidiPorts;
+
+// MIDI ports that aren't there, which just remember what was sent so it can be checked
+#[derive(Default)]
+pub struct MockMidi {
+    pub sent: RefCell<Vec<Message>>,
+    pub drains: Cell<usize>,
+
+    // how many sends from now on fail, as they would with the output buffer full
+    pub failing: Cell<usize>,
+}
+
+impl MockMidi {
+    // everything sent so far, forgetting it
+    pub fn take_sent(&self) -> Vec<Message> {
+        self.sent.borrow_mut().drain(..).collect()
+    }
+}
+
+impl MidiPorts for MockMidi {
+    fn send(&self, msg: &Message) -> Result<(), ()> {
+        if self.failing.get() > 0 {
+            self.failing.set(self.failing.get() - 1);
+            return Err(());
+        }
+
+        self.sent.borrow_mut().push(msg.clone());
+        Ok(())
+    }
+
+    fn drain(&self) {
+        self.drains.set(self.drains.get() + 1);
+    }
+
+    // nothing ever comes in
+    fn input_fd(&self) -> Option<RawFd> {
+        None
+    }
+
+    fn pass_thru(&self, _: &[EventKind]) {}
+}
@@ -0,0 +1,59 @@
+//  maschine.rs: user-space drivers for native instruments USB HIDs
+//  Copyright (C) 2015 William Light <wrl@illest.net>
+//
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU Lesser General Public License as
+//  published by the Free Software Foundation, either version 3 of the
+//  License, or (at your option) any later version.
+//
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU Lesser General Public License for more details.
+//
+//  You should have received a copy of the GNU Lesser General Public
+//  License along with this program.  If not, see
+//  <http://www.gnu.org/licenses/>.
+
+// OSC over UDP
+
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::panic;
+
+use tinyosc as osc;
+
+// as much as a UDP datagram can hold, so that nothing is cut short: a whole /maschine/leds/set
+// frame is a couple of hundred bytes, and a sysex can be far longer
+pub const OSC_RECV_BUFFER: usize = 65536;
+
+// the next datagram on the socket and how many bytes it was, the message None if it isn't one
+pub fn recv_osc<'a>(
+    socket: &UdpSocket,
+    buf: &'a mut [u8],
+) -> io::Result<(usize, Option<osc::Message<'a>>)> {
+    let (nbytes, _) = socket.recv_from(buf)?;
+
+    // tinyosc panics rather than erroring on some truncated arguments, which is no
+    // reason to go through the whole panic recovery
+    let packet: &'a [u8] = &buf[..nbytes];
+    match panic::catch_unwind(|| osc::Message::deserialize(packet)) {
+        Ok(Ok(msg)) => Ok((nbytes, Some(msg))),
+        _ => Ok((nbytes, None)),
+    }
+}
+
+// the message to each of the addresses, `repeats` times over. one client going away mustn't
+// cut the others off, so errors are only logged
+pub fn send_osc(socket: &UdpSocket, addrs: &[SocketAddr], msg: &osc::Message, repeats: u32) {
+    let packet = msg.serialize().unwrap();
+
+    for addr in addrs {
+        for _ in 0..repeats {
+            match socket.send_to(&packet, addr) {
+                Ok(_) => {}
+                Err(e) => println!(" :: error in send_to {}: {}", addr, e),
+            }
+        }
+    }
+}